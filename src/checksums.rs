@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, trace};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::sign_file;
+use crate::publish::write_atomically;
+use crate::signatures::signature_path;
+
+/// File extension of the archives in the artifacts directory, which checksum files cover.
+pub(crate) const ARCHIVE_EXTENSION: &str = "tar";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+/// Hash algorithms for which a checksum file is written to the artifacts directory.
+pub(crate) enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    /// Every supported algorithm.
+    pub(crate) const ALL: [ChecksumAlgorithm; 3] = [
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Sha512,
+        ChecksumAlgorithm::Blake3,
+    ];
+
+    /// Name of the checksum file for this algorithm, as used by `sha256sum`, `sha512sum` and
+    /// `b3sum`.
+    #[must_use]
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "SHA256SUMS",
+            ChecksumAlgorithm::Sha512 => "SHA512SUMS",
+            ChecksumAlgorithm::Blake3 => "BLAKE3SUMS",
+        }
+    }
+
+    /// Computes the digest of `data`, as a lowercase hexadecimal string.
+    #[must_use]
+    pub(crate) fn digest_hex(self, data: &[u8]) -> String {
+        match self {
+            ChecksumAlgorithm::Sha256 => sha256_hex(data),
+            ChecksumAlgorithm::Sha512 => to_hex(&Sha512::digest(data)),
+            ChecksumAlgorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+}
+
+/// The checksum algorithms used if `options.checksums` is not set.
+#[must_use]
+pub(crate) fn default_checksums() -> Vec<ChecksumAlgorithm> {
+    vec![ChecksumAlgorithm::Sha256]
+}
+
+/// Formats `bytes` as a lowercase hexadecimal string.
+#[must_use]
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Computes the SHA-256 digest of `data`, as a lowercase hexadecimal string.
+#[must_use]
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Formats the contents of a checksum file for the given `(file name, file contents)` pairs, in
+/// the format understood by `sha256sum --check` and friends: One `<digest>  <file name>` line per
+/// file.
+#[must_use]
+pub(crate) fn format_checksum_file(
+    algorithm: ChecksumAlgorithm,
+    files: &[(String, Vec<u8>)],
+) -> String {
+    files
+        .iter()
+        .map(|(name, contents)| format!("{}  {name}\n", algorithm.digest_hex(contents)))
+        .collect()
+}
+
+/// Parses the contents of a checksum file written by [format_checksum_file] into
+/// `(digest, file name)` pairs. Lines which are not in the `<digest>  <file name>` format are
+/// skipped. The binary mode marker `*` written by some tools is accepted as well.
+#[must_use]
+pub(crate) fn parse_checksum_file(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter_map(|(digest, name)| {
+            let name = name.strip_prefix([' ', '*'])?;
+            (!digest.is_empty() && !name.is_empty()).then_some((digest, name))
+        })
+        .collect()
+}
+
+/// Reads every archive in `dir`, sorted by file name.
+fn read_archives(dir: &Path) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let mut archives = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file()
+            || path
+                .extension()
+                .is_none_or(|extension| extension != ARCHIVE_EXTENSION)
+        {
+            continue;
+        }
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        trace!("Reading {path:?} for checksumming");
+        archives.push((name, std::fs::read(&path)?));
+    }
+    archives.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(archives)
+}
+
+/// Writes one checksum file per algorithm in `options.checksums` into `dir`, covering every
+/// archive in it, and signs each checksum file with the signing key. Returns the paths of all
+/// written checksum files. Nothing is written if `dir` does not contain any archives.
+///
+/// The signature and the checksum file are each replaced atomically, but not together: The
+/// signature is written first, so a reader in between sees the new signature next to the old
+/// checksum file, which fails verification instead of being trusted.
+pub(crate) fn write_checksum_files(config: &ConfigFile, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let archives = read_archives(dir)?;
+    if archives.is_empty() {
+        debug!("No archives in {dir:?}, not writing any checksum files");
+        return Ok(Vec::new());
+    }
+    let mut algorithms = config.options.checksums.clone();
+    algorithms.sort();
+    algorithms.dedup();
+    let timestamp = iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string();
+    let mut written = Vec::with_capacity(algorithms.len());
+    for algorithm in algorithms {
+        let contents = format_checksum_file(algorithm, &archives);
+        let path = dir.join(algorithm.file_name());
+        let trusted_comment = format!("timestamp:{timestamp}\tfile:{}", algorithm.file_name());
+        let signature = sign_file(config, contents.as_bytes(), Some(&trusted_comment))?;
+        write_atomically(&signature_path(&path), &signature)?;
+        write_atomically(&path, contents.as_bytes())?;
+        debug!(
+            "Wrote and signed {path:?}, covering {} archives",
+            archives.len()
+        );
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use minisign::{KeyPair, PublicKey};
+
+    use super::{
+        ChecksumAlgorithm, format_checksum_file, parse_checksum_file, sha256_hex,
+        write_checksum_files,
+    };
+    use crate::config::ConfigFile;
+    use crate::signatures::verify_file;
+    use crate::temp_dir::TempDir;
+    use crate::{SECRET, check_minisign};
+
+    #[test]
+    fn sha256_of_known_input() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn formats_checksum_file_like_coreutils() {
+        let files = vec![
+            (String::from("a.tar"), b"abc".to_vec()),
+            (String::from("b.tar"), Vec::new()),
+        ];
+        assert_eq!(
+            format_checksum_file(ChecksumAlgorithm::Blake3, &files),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85  a.tar\n\
+             af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262  b.tar\n"
+        );
+        assert!(
+            format_checksum_file(ChecksumAlgorithm::Sha512, &files).starts_with("ddaf35a193617aba")
+        );
+        let contents = format_checksum_file(ChecksumAlgorithm::Sha256, &files);
+        let parsed = parse_checksum_file(&contents);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0], (sha256_hex(b"abc").as_str(), "a.tar"));
+        assert_eq!(
+            parse_checksum_file("00 *b.tar\ngarbage\n"),
+            [("00", "b.tar")]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires minisign"]
+    fn writes_signed_checksum_files() {
+        check_minisign().unwrap();
+        let public_key = PublicKey::from_secret_key(
+            SECRET.get_or_init(|| KeyPair::generate_unencrypted_keypair().unwrap().sk),
+        )
+        .unwrap();
+        let dir = TempDir::new("checksums-test").unwrap();
+        let config: ConfigFile = toml::from_str(&format!(
+            "[crates]\n[options]\nworkspace_path = {:?}\nverifying_key = \"{}\"\nautodelete_sources = false\nchecksums = [\"sha256\", \"blake3\"]\n[dependencies]\n",
+            &*dir,
+            public_key.to_base64()
+        ))
+        .unwrap();
+        std::fs::write(dir.join("tool.tar"), b"abc").unwrap();
+        std::fs::write(dir.join("SHA256SUMS"), b"stale").unwrap();
+        let written = write_checksum_files(&config, &dir).unwrap();
+        assert_eq!(written, [dir.join("SHA256SUMS"), dir.join("BLAKE3SUMS")]);
+        for path in &written {
+            assert!(verify_file(&public_key, path).is_ok());
+        }
+        assert!(
+            std::fs::read_to_string(dir.join("SHA256SUMS"))
+                .unwrap()
+                .starts_with(&sha256_hex(b"abc"))
+        );
+        // No temporary file is left behind
+        assert_eq!(std::fs::read_dir(&*dir).unwrap().count(), 5);
+    }
+}
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::output::OutputFormat;
+
 #[derive(Debug, clap::Parser, zeroize::Zeroize, zeroize::ZeroizeOnDrop)]
 #[command(name = "warehouseify")]
 #[command(version, long_about = None)]
@@ -10,11 +12,26 @@ pub struct Args {
     /// Path to a warehouseify config file. If not specified, will use default values.
     pub(crate) config: Option<PathBuf>,
     #[arg(long, value_name = "MINISIGN_KEY")]
-    /// Minisign secret key, used to sign the resulting binstall-ready crate. Provide it here, or in the warehousify config file under options.signing_key. Only supports encrypted secret keys.
+    /// Minisign secret key, used to sign the resulting binstall-ready crate. Prefer
+    /// "--signing-key-file", "--signing-key-fd" or $WAREHOUSE_SECRET, since arguments are visible
+    /// to other users. Unencrypted secret keys are only accepted if
+    /// "options.allow_unencrypted_signing_key" is set.
     pub(crate) signing_key: Option<String>,
-    #[arg(short = 'p', long, value_name = "MINISIGN_KEY")]
-    /// Minisign secret key password, used to unlock the signing key.
-    pub(crate) signing_key_password: String,
+    #[arg(long, value_name = "FILE")]
+    #[zeroize(skip)]
+    /// Path to a file containing the minisign secret key.
+    pub(crate) signing_key_file: Option<PathBuf>,
+    #[arg(long, value_name = "FD")]
+    /// File descriptor to read the minisign secret key from.
+    pub(crate) signing_key_fd: Option<i32>,
+    #[arg(short = 'p', long, value_name = "PASSWORD")]
+    /// Minisign secret key password, used to unlock the signing key. Prefer
+    /// "--signing-key-password-fd", $WAREHOUSE_SECRET_PASSWORD or the interactive prompt, since
+    /// arguments are visible to other users.
+    pub(crate) signing_key_password: Option<String>,
+    #[arg(long, value_name = "FD")]
+    /// File descriptor to read the minisign secret key password from. Only the first line is read.
+    pub(crate) signing_key_password_fd: Option<i32>,
     #[arg(short = 'v', long, action = clap::ArgAction::Count)]
     /// Turn on verbose logging. The default log level is "INFO".
     /// Each instance of "v" in "-v" will increase the logging level by one. Logging levels are
@@ -36,4 +53,124 @@ pub struct Args {
     /// When installing dependencies, pass the "--force" argument to cargo.
     #[arg(long, default_value_t = false)]
     pub(crate) force: bool,
+    /// Do not stop at the first crate which fails to build or sign. Instead, process all crates
+    /// and print a summary of what failed and why at the end.
+    #[arg(short = 'k', long, default_value_t = false)]
+    pub(crate) keep_going: bool,
+    /// After all crates have been built and signed successfully, publish the artifacts with the
+    /// publisher configured in the "[publish]" section of the config file.
+    #[arg(long, default_value_t = false)]
+    pub(crate) publish: bool,
+    /// How to report the result of the run on stdout. "human" prints a summary table, "json" and
+    /// "toml" print a machine readable report including checksums, artifact paths and durations.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    #[zeroize(skip)]
+    pub(crate) output: OutputFormat,
+    #[command(subcommand)]
+    #[zeroize(skip)]
+    /// What to do instead of building and signing all crates.
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum Command {
+    /// Verify artifacts with the verifying key from the config file. For a single archive, display
+    /// the authenticated trusted comment of its' signature. For a directory, check every archive
+    /// against its' signature and the checksum files, and report orphaned or unsigned files.
+    /// Exits with a nonzero exit code on any discrepancy.
+    Verify {
+        /// Path to an archive, whose signature is expected at `<PATH>.sig`, or to an artifacts
+        /// directory. Defaults to the artifacts directory of the workspace.
+        path: Option<PathBuf>,
+    },
+    /// Rotate the signing key: Write a key transition statement signed with the retired key, then
+    /// re-sign every artifact signed with a key from "options.retired_verifying_keys" with the
+    /// active signing key. The active signing key is loaded like it is for regular runs.
+    RotateKey {
+        /// Path to a file containing the retired minisign secret key.
+        #[arg(long, value_name = "FILE")]
+        retired_signing_key_file: PathBuf,
+        /// File descriptor to read the password of the retired secret key from. If not given, the
+        /// password is prompted for.
+        #[arg(long, value_name = "FD")]
+        retired_signing_key_password_fd: Option<i32>,
+    },
+    /// Publish the most recent signed archive of every crate version and target in the artifacts
+    /// directory with the publisher configured in the "[publish]" section of the config file,
+    /// without building anything. Already published files are not uploaded again.
+    Publish,
+    /// Serve the artifacts directory over HTTP, to test installing crates before publishing them.
+    /// For the most recent version of each crate, a ready-to-run `cargo binstall --manifest-path`
+    /// command is printed, which installs the crate from this server.
+    Serve {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+        /// Port to listen on. 0 picks a free port.
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// Set up a new warehouse in the current directory: Generate a minisign key pair, write a
+    /// commented config file and add the workspace and the secret key to ".gitignore". Asks for
+    /// everything not given as a flag, unless "--no-confirm" is set. The password of the new key
+    /// is read like the one of the signing key for regular runs, or prompted for.
+    Init {
+        /// Instead of setting up a new warehouse, write a GitHub Actions workflow for the existing
+        /// config file to ".github/workflows/warehouse.yml", which builds all crates on one runner
+        /// per target in "options.targets" and publishes them with the publisher configured in
+        /// the "[publish]" section of the config file.
+        #[arg(long, conflicts_with_all = ["workspace_path", "targets", "pkg_url", "cargo_auditable"])]
+        github_actions: bool,
+        /// Directory sources are built in and archives are written to. Defaults to "./warehouse/".
+        #[arg(long, value_name = "PATH")]
+        workspace_path: Option<PathBuf>,
+        /// Target to build for on GitHub Actions. Can be given multiple times. Defaults to
+        /// "x86_64-unknown-linux-gnu".
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// The "pkg-url" cargo-binstall downloads archives from. Not needed if a publisher is
+        /// configured later.
+        #[arg(long, value_name = "URL")]
+        pkg_url: Option<String>,
+        /// Whether to build crates with cargo-auditable. Defaults to "true".
+        #[arg(long, value_name = "BOOL")]
+        cargo_auditable: Option<bool>,
+    },
+    /// Add a crate to the "[crates]" section of the config file, keeping its' formatting and
+    /// comments. Crates from crates.io are pinned to the newest version matching the requirement.
+    Add {
+        /// "<NAME>" or "<NAME>@<VERSION REQUIREMENT>" for crates from crates.io. Optional for
+        /// crates from a path, whose name is read from their Cargo.toml, and from a git
+        /// repository, whose name defaults to the name of the repository.
+        #[arg(value_name = "CRATE[@VERSION]", required_unless_present_any = ["git", "path"])]
+        spec: Option<String>,
+        /// URL of a git repository to build the crate from.
+        #[arg(long, value_name = "URL", conflicts_with = "path")]
+        git: Option<String>,
+        /// Tag of the git repository to build.
+        #[arg(long, requires = "git", conflicts_with_all = ["branch", "rev"])]
+        tag: Option<String>,
+        /// Branch of the git repository to build.
+        #[arg(long, requires = "git", conflicts_with = "rev")]
+        branch: Option<String>,
+        /// Commit of the git repository to build.
+        #[arg(long, requires = "git")]
+        rev: Option<String>,
+        /// Directory containing the crate to build.
+        #[arg(long, value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+    /// Remove a crate from the "[crates]" section of the config file.
+    Remove {
+        /// Name of the crate, as it appears in "[crates]".
+        #[arg(value_name = "CRATE")]
+        name: String,
+    },
+    /// Bump crates from crates.io in the "[crates]" section of the config file to the newest
+    /// version compatible with their current version.
+    Update {
+        /// Name of the crate to update. Updates all crates from crates.io if not given.
+        #[arg(value_name = "CRATE")]
+        name: Option<String>,
+    },
 }
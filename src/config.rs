@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 use std::os::unix::prelude::MetadataExt;
@@ -8,6 +8,8 @@ use cargo_toml::DepsSet;
 use serde::Deserialize;
 
 use crate::StdError;
+use crate::checksums::{ChecksumAlgorithm, default_checksums};
+use crate::publish::PublishConfig;
 
 #[derive(Deserialize, Debug)]
 /// Represents the structure of the `config.toml` configuration file.
@@ -20,6 +22,13 @@ pub(crate) struct ConfigFile {
     /// user cannot add their own dependencies, but can toggle whether they are used and if so, which
     /// version to use.
     pub(crate) dependencies: DependenciesConfig,
+    /// Per-crate settings, which take precedence over the ones set in [OptionsConfig]. Keyed by
+    /// the package name of the crate.
+    #[serde(default)]
+    pub(crate) overrides: HashMap<String, CrateOverrides>,
+    /// Where `warehouseify publish` uploads signed archives to.
+    #[serde(default)]
+    pub(crate) publish: Option<PublishConfig>,
 }
 
 impl ConfigFile {
@@ -39,6 +48,64 @@ impl ConfigFile {
         file.read_to_string(&mut contents)?;
         Ok(toml::from_str(&contents)?)
     }
+
+    /// The Rust toolchain a crate should be built with. A toolchain set in the crate's
+    /// [CrateOverrides] takes precedence over `options.toolchain`. Returns [None], if the
+    /// toolchain on the users' `PATH` should be used.
+    #[must_use]
+    pub(crate) fn toolchain(&self, crate_name: &str) -> Option<&str> {
+        self.overrides
+            .get(crate_name)
+            .and_then(|overrides| overrides.toolchain.as_deref())
+            .or(self.options.toolchain.as_deref())
+    }
+
+    /// The environment variables a crate should be built with. Variables set in the crate's
+    /// [CrateOverrides] take precedence over the ones set in `options.env`.
+    #[must_use]
+    pub(crate) fn env(&self, crate_name: &str) -> BTreeMap<String, String> {
+        let mut env = self.options.env.clone();
+        if let Some(overrides) = self.overrides.get(crate_name) {
+            env.extend(overrides.env.clone());
+        }
+        env
+    }
+
+    /// The [BuildLimits] for a crate. Each limit set in the crate's [CrateOverrides] takes
+    /// precedence over the same limit set in `options.limits`.
+    #[must_use]
+    pub(crate) fn limits(&self, crate_name: &str) -> BuildLimits {
+        let global = self.options.limits;
+        match self.overrides.get(crate_name) {
+            Some(overrides) => BuildLimits {
+                timeout: overrides.limits.timeout.or(global.timeout),
+                memory: overrides.limits.memory.or(global.memory),
+                cpu_time: overrides.limits.cpu_time.or(global.cpu_time),
+            },
+            None => global,
+        }
+    }
+
+    /// The `pkg-url` written into the Cargo.toml of each crate for cargo-binstall. If a publisher
+    /// is configured, the `pkg-url` matching where it uploads archives to is used, otherwise
+    /// `options.pkg_url`.
+    #[must_use]
+    pub(crate) fn pkg_url(&self) -> String {
+        match &self.publish {
+            Some(publish) => publish.pkg_url(),
+            None => self.options.pkg_url.clone(),
+        }
+    }
+
+    /// All distinct toolchains referenced anywhere in the config file.
+    #[must_use]
+    pub(crate) fn toolchains(&self) -> HashSet<String> {
+        self.overrides
+            .values()
+            .filter_map(|overrides| overrides.toolchain.clone())
+            .chain(self.options.toolchain.clone())
+            .collect()
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -54,9 +121,73 @@ pub(crate) struct OptionsConfig {
     #[zeroize(skip)]
     pub(crate) workspace_path: PathBuf,
     pub(crate) signing_key: Option<String>,
+    /// Path to a file containing the minisign secret key. Takes precedence over `signing_key`.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) signing_key_file: Option<PathBuf>,
+    /// Whether unencrypted minisign secret keys may be used for signing. Only meant for ephemeral
+    /// CI runners, which receive the key from a secret store.
+    #[serde(default)]
+    pub(crate) allow_unencrypted_signing_key: bool,
     pub(crate) verifying_key: String,
+    /// Verifying keys which were used to sign artifacts before `verifying_key`. Artifacts signed
+    /// with one of these keys can be re-signed with the current key via `warehouseify rotate-key`.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) retired_verifying_keys: Vec<String>,
     pub(crate) autodelete_sources: bool,
+    /// Targets the workflow written by `warehouseify init --github-actions` builds all crates for,
+    /// each on a GitHub-hosted runner of that architecture. warehouseify itself always builds for
+    /// the host. Defaults to `["x86_64-unknown-linux-gnu"]`.
+    #[serde(default = "default_targets")]
+    #[zeroize(skip)]
+    pub(crate) targets: Vec<String>,
+    /// The `pkg-url` for cargo-binstall, if no publisher is configured in `[publish]`.
+    #[serde(default)]
     pub(crate) pkg_url: String,
+    /// A `CARGO_TARGET_DIR` shared between all crates, so that common dependencies only have to be
+    /// compiled once per run. If not set, each crate is built into its own `target` directory.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) target_dir: Option<PathBuf>,
+    /// The Rust toolchain to build all crates with, e.g. `1.86.0` or `nightly-2025-04-01`. Invokes
+    /// `cargo +<toolchain>`, which requires `rustup`. If not set, the `cargo` on `PATH` is used.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) toolchain: Option<String>,
+    /// Environment variables to set when building crates, such as `RUSTFLAGS`.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) env: BTreeMap<String, String>,
+    /// Whether builds inherit the full environment of the host. If `false`, only a small set of
+    /// variables required to locate the toolchain, such as `PATH` and `HOME`, is passed through.
+    #[serde(default)]
+    pub(crate) inherit_env: bool,
+    /// Whether to vendor all dependencies of a crate up front and then build it offline, inside of
+    /// a [bubblewrap](https://github.com/containers/bubblewrap) sandbox without network access,
+    /// which may only write to the crates' source and target directories.
+    #[serde(default)]
+    pub(crate) sandbox: bool,
+    /// Timeouts and resource limits applied to all builds.
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) limits: BuildLimits,
+    /// Hash algorithms to write checksum files for, each covering every archive in the artifacts
+    /// directory. Any of `sha256`, `sha512` and `blake3`. Defaults to `["sha256"]`.
+    #[serde(default = "default_checksums")]
+    #[zeroize(skip)]
+    pub(crate) checksums: Vec<ChecksumAlgorithm>,
+    /// Template for the trusted comment of archive signatures. The placeholders `{file}`,
+    /// `{crate}`, `{version}`, `{target}`, `{timestamp}` and `{source_sha256}` are replaced with
+    /// the details of the archive. Defaults to
+    /// [DEFAULT_TRUSTED_COMMENT](crate::signatures::DEFAULT_TRUSTED_COMMENT).
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub(crate) trusted_comment: Option<String>,
+}
+
+fn default_targets() -> Vec<String> {
+    vec![String::from("x86_64-unknown-linux-gnu")]
 }
 
 #[derive(Deserialize, Debug)]
@@ -73,3 +204,82 @@ pub(crate) struct DependencyProperties {
     /// The version identifier of the dependency
     pub(crate) version: String,
 }
+
+#[derive(Debug, Deserialize, Default)]
+pub(crate) struct CrateOverrides {
+    /// Overrides `options.toolchain` for this crate.
+    pub(crate) toolchain: Option<String>,
+    /// Environment variables to set when building this crate, in addition to `options.env`.
+    #[serde(default)]
+    pub(crate) env: BTreeMap<String, String>,
+    /// Overrides the limits set in `options.limits` for this crate.
+    #[serde(default)]
+    pub(crate) limits: BuildLimits,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+/// Timeouts and resource limits for building a crate. Unset limits are not enforced.
+pub(crate) struct BuildLimits {
+    /// Wall clock time in seconds, after which the entire build is killed.
+    pub(crate) timeout: Option<u64>,
+    /// Maximum address space of every single build process, in MiB.
+    pub(crate) memory: Option<u64>,
+    /// Maximum CPU time of every single build process, in seconds.
+    pub(crate) cpu_time: Option<u64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConfigFile;
+
+    /// A config with no crates and the given additional `options` and `overrides` tables.
+    fn config(options: &str, overrides: &str) -> ConfigFile {
+        toml::from_str(&format!(
+            "[crates]\n[options]\nworkspace_path = \"./warehouse/\"\nverifying_key = \"\"\nautodelete_sources = false\n{options}\n[dependencies]\n{overrides}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn overridden_toolchain_takes_precedence() {
+        let config = config(
+            "toolchain = \"1.86.0\"",
+            "[overrides.pinned]\ntoolchain = \"1.70.0\"\n[overrides.other]\nenv = { A = \"a\" }",
+        );
+        assert_eq!(config.toolchain("pinned"), Some("1.70.0"));
+        assert_eq!(config.toolchain("other"), Some("1.86.0"));
+        assert_eq!(config.toolchain("unknown"), Some("1.86.0"));
+    }
+
+    #[test]
+    fn toolchain_defaults_to_none() {
+        let config = config("", "[overrides.other]\nenv = { A = \"a\" }");
+        assert_eq!(config.toolchain("other"), None);
+        assert_eq!(config.toolchain("unknown"), None);
+        assert!(config.toolchains().is_empty());
+    }
+
+    #[test]
+    fn overridden_env_takes_precedence() {
+        let config = config(
+            "[options.env]\nRUSTFLAGS = \"-C opt-level=3\"\nSHARED = \"global\"",
+            "[overrides.tool.env]\nRUSTFLAGS = \"-C target-cpu=native\"\nONLY_TOOL = \"tool\"",
+        );
+        assert_eq!(
+            config.env("tool"),
+            [
+                ("ONLY_TOOL", "tool"),
+                ("RUSTFLAGS", "-C target-cpu=native"),
+                ("SHARED", "global"),
+            ]
+            .map(|(key, value)| (String::from(key), String::from(value)))
+            .into()
+        );
+        assert_eq!(
+            config.env("other"),
+            [("RUSTFLAGS", "-C opt-level=3"), ("SHARED", "global")]
+                .map(|(key, value)| (String::from(key), String::from(value)))
+                .into()
+        );
+    }
+}
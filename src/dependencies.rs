@@ -3,8 +3,8 @@ use std::process::{Command, ExitStatus, Output, Stdio};
 
 use semver::{Version, VersionReq};
 
-use crate::StdError;
 use crate::config::DependenciesConfig;
+use crate::{StdError, StdErrorS};
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub(crate) struct Crate {
@@ -99,7 +99,10 @@ pub(crate) fn list_missing_dependencies(
                     log::error!(
                         "Misformated dependency found in your configuration file. This is likely not a bug with warehouseify. Exception: {e}"
                     );
-                    panic!("Aborting: Dependency in config file is misformated")
+                    return Err(format!(
+                        "Dependency {required_dependency_name} in config file is misformated: {e}"
+                    )
+                    .into());
                 }
             };
             if !config_required_version_semver.matches(&installed_version_semver) {
@@ -207,3 +210,154 @@ fn get_installed_crates_on_host() -> Result<HashMap<String, Crate>, StdError<'st
     }
     Ok(installed_crates)
 }
+
+/// Executes `rustc -vV` on the host and returns its' trimmed output. If a `toolchain` is given,
+/// `rustc +<toolchain> -vV` is executed instead, which requires `rustup`.
+pub(crate) fn rustc_version_verbose(toolchain: Option<&str>) -> Result<String, StdErrorS> {
+    let mut command = Command::new("rustc");
+    if let Some(toolchain) = toolchain {
+        command.arg(format!("+{toolchain}"));
+    }
+    let output = command.arg("-vV").output()?;
+    if !output.status.success() {
+        log::error!(
+            "rustc -vV exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(String::from(
+            "Executing 'rustc -vV' failed on the host. Is rustc available on PATH?",
+        )
+        .into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Extracts the host target triple from the output of `rustc -vV`.
+#[must_use]
+pub(crate) fn host_triple(rustc_version_verbose: &str) -> Option<&str> {
+    rustc_version_verbose
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::trim)
+}
+
+/// Checks which of the given Rust `toolchains` are not installed via `rustup` on the host. A
+/// toolchain counts as installed, if `rustup toolchain list` lists it either verbatim, or suffixed
+/// with the host target triple.
+///
+/// ## Requirements
+///
+/// Requires `rustup` to be installed on the host system and on the user's `PATH`, unless
+/// `toolchains` is empty.
+pub(crate) fn list_missing_toolchains(
+    toolchains: &HashSet<String>,
+) -> Result<HashSet<String>, StdErrorS> {
+    if toolchains.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let output = Command::new("rustup")
+        .arg("toolchain")
+        .arg("list")
+        .output()?;
+    if !output.status.success() {
+        log::error!(
+            "rustup toolchain list exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(String::from(
+            "Executing 'rustup toolchain list' failed on the host. Pinned toolchains require rustup to be available on PATH.",
+        )
+        .into());
+    }
+    let rustc_version = rustc_version_verbose(None)?;
+    let host = host_triple(&rustc_version).unwrap_or_default();
+    let stdout = String::from_utf8(output.stdout)?;
+    let missing = missing_toolchains(toolchains, &stdout, host);
+    log::debug!("Determined the following toolchains to be missing on the host: {missing:?}");
+    Ok(missing)
+}
+
+/// Returns which of the given `toolchains` are not listed in the output of
+/// `rustup toolchain list`, see [list_missing_toolchains].
+#[must_use]
+fn missing_toolchains(
+    toolchains: &HashSet<String>,
+    rustup_toolchain_list: &str,
+    host: &str,
+) -> HashSet<String> {
+    // Lines may be suffixed with e.g. "(default)" or "(active, default)"
+    let installed_toolchains = rustup_toolchain_list
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect::<HashSet<&str>>();
+    log::trace!("Toolchains installed on host: {installed_toolchains:?}");
+    toolchains
+        .iter()
+        .filter(|toolchain| {
+            !installed_toolchains.contains(toolchain.as_str())
+                && !installed_toolchains.contains(format!("{toolchain}-{host}").as_str())
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{host_triple, missing_toolchains};
+
+    #[test]
+    fn parses_host_triple() {
+        let rustc_version = "rustc 1.86.0 (05f9846f8 2025-03-31)
+binary: rustc
+commit-hash: 05f9846f893b09a1be1fc8560e33fc3c815cfecb
+commit-date: 2025-03-31
+host: aarch64-unknown-linux-gnu
+release: 1.86.0
+LLVM version: 19.1.7";
+        assert_eq!(
+            host_triple(rustc_version),
+            Some("aarch64-unknown-linux-gnu")
+        );
+        assert_eq!(host_triple("rustc 1.86.0\nbinary: rustc"), None);
+    }
+
+    #[test]
+    fn finds_missing_toolchains() {
+        let rustup_toolchain_list = "stable-x86_64-unknown-linux-gnu (active, default)
+nightly-2025-01-01-x86_64-unknown-linux-gnu
+1.86.0-x86_64-unknown-linux-gnu (default)
+custom
+";
+        let toolchains = [
+            "stable",
+            "nightly-2025-01-01",
+            "1.86.0",
+            "custom",
+            "1.70.0",
+            "beta",
+        ]
+        .map(String::from)
+        .into_iter()
+        .collect::<HashSet<String>>();
+        assert_eq!(
+            missing_toolchains(
+                &toolchains,
+                rustup_toolchain_list,
+                "x86_64-unknown-linux-gnu"
+            ),
+            HashSet::from([String::from("1.70.0"), String::from("beta")])
+        );
+        assert_eq!(
+            missing_toolchains(
+                &HashSet::from([String::from("stable")]),
+                rustup_toolchain_list,
+                "aarch64-unknown-linux-gnu"
+            ),
+            HashSet::from([String::from("stable")])
+        );
+    }
+}
@@ -0,0 +1,535 @@
+use std::path::Path;
+
+use log::{debug, error, info, warn};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use toml_edit::{DocumentMut, InlineTable, Item, Table, TableLike, Value};
+
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::sort_crates_into_buckets;
+use crate::publish::write_if_changed;
+
+/// Where a crate added with `warehouseify add` comes from.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CrateSource {
+    /// crates.io, pinned to the newest version matching the requirement.
+    CratesIo(VersionReq),
+    /// A git repository, optionally at a tag, branch or commit, given as `(key, value)`.
+    Git(String, Option<(&'static str, String)>),
+    /// A directory containing the crate.
+    Path(String),
+}
+
+/// A version of a crate in the crates.io index.
+#[derive(Debug, Deserialize)]
+struct IndexVersion {
+    vers: String,
+    yanked: bool,
+}
+
+/// Returns `true` if `name` is a valid name for a crate on crates.io.
+#[must_use]
+fn is_valid_crate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Path of the crate `name` in the sparse crates.io index. `name` must be a valid crate name, see
+/// [is_valid_crate_name].
+#[must_use]
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+/// The newest version in the crates.io index file `index` which matches `requirement` and has not
+/// been yanked.
+#[must_use]
+fn newest_matching(index: &str, requirement: &VersionReq) -> Option<Version> {
+    index
+        .lines()
+        .filter_map(|line| serde_json::from_str::<IndexVersion>(line).ok())
+        .filter(|version| !version.yanked)
+        .filter_map(|version| Version::parse(&version.vers).ok())
+        .filter(|version| requirement.matches(version))
+        .max()
+}
+
+/// Looks up the newest version of the crate `name` on crates.io which matches `requirement`.
+#[cfg(feature = "http-client")]
+fn newest_version(name: &str, requirement: &VersionReq) -> Result<Version, Error> {
+    let url = format!("https://index.crates.io/{}", index_path(name));
+    debug!("Looking up the versions of {name} at {url}");
+    let response = minreq::get(&url)
+        .with_header(
+            "User-Agent",
+            concat!("warehouseify/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .map_err(|e| Error::Download(Box::new(e)))?;
+    match response.status_code {
+        200 => (),
+        404 => {
+            return Err(Error::Config(
+                format!("there is no crate named {name} on crates.io").into(),
+            ));
+        }
+        status => {
+            return Err(Error::Download(
+                format!("looking up {name} on crates.io failed with HTTP status {status}").into(),
+            ));
+        }
+    }
+    let index = response
+        .as_str()
+        .map_err(|e| Error::Download(Box::new(e)))?;
+    newest_matching(index, requirement).ok_or_else(|| {
+        Error::Config(format!("no version of {name} on crates.io matches {requirement}").into())
+    })
+}
+
+#[cfg(not(feature = "http-client"))]
+fn newest_version(_name: &str, _requirement: &VersionReq) -> Result<Version, Error> {
+    Err(Error::Config(
+        String::from("looking up crates on crates.io requires the http-client feature").into(),
+    ))
+}
+
+/// Splits `spec` given to `warehouseify add` into the name of the crate and its' source. `spec` is
+/// `<name>` or `<name>@<version requirement>` for crates from crates.io and may be left out for
+/// crates from a path, whose name is read from their Cargo.toml, or from a git repository, whose
+/// name defaults to the name of the repository.
+pub(crate) fn parse_source(
+    spec: Option<&str>,
+    git: Option<&str>,
+    reference: Option<(&'static str, String)>,
+    path: Option<&Path>,
+) -> Result<(String, CrateSource), Error> {
+    let (name, requirement) = match spec.map(|spec| spec.split_once('@')) {
+        Some(Some((name, requirement))) => (Some(name.to_owned()), Some(requirement)),
+        Some(None) => (spec.map(str::to_owned), None),
+        None => (None, None),
+    };
+    if requirement.is_some() && (git.is_some() || path.is_some()) {
+        return Err(Error::Config(
+            String::from("versions can only be given for crates from crates.io").into(),
+        ));
+    }
+    let (name, source) = match (git, path) {
+        (Some(url), _) => {
+            let name = name.unwrap_or_else(|| {
+                url.trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .trim_end_matches(".git")
+                    .to_owned()
+            });
+            (name, CrateSource::Git(url.to_owned(), reference))
+        }
+        (None, Some(path)) => {
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    let manifest = cargo_toml::Manifest::from_path(path.join("Cargo.toml"))
+                        .map_err(|e| {
+                            Error::Config(
+                                format!("cannot read {:?}: {e}", path.join("Cargo.toml")).into(),
+                            )
+                        })?;
+                    manifest
+                        .package
+                        .map(|package| package.name)
+                        .ok_or_else(|| {
+                            Error::Config(format!("{path:?} does not contain a package").into())
+                        })?
+                }
+            };
+            (name, CrateSource::Path(path.to_string_lossy().into_owned()))
+        }
+        (None, None) => {
+            let Some(name) = name else {
+                return Err(Error::Config(
+                    String::from("name the crate to add, or pass --git or --path").into(),
+                ));
+            };
+            let requirement = VersionReq::parse(requirement.unwrap_or("*")).map_err(|e| {
+                Error::Config(format!("invalid version requirement for {name}: {e}").into())
+            })?;
+            (name, CrateSource::CratesIo(requirement))
+        }
+    };
+    match is_valid_crate_name(&name) {
+        true => Ok((name, source)),
+        false => Err(Error::Config(
+            format!("{name:?} is not a valid crate name").into(),
+        )),
+    }
+}
+
+/// Reads the config file at `path` for editing.
+fn read_document(path: &Path) -> Result<DocumentMut, Error> {
+    std::fs::read_to_string(path)?
+        .parse::<DocumentMut>()
+        .map_err(|e| Error::Config(Box::new(e)))
+}
+
+/// The `[crates]` table of `document`, which is created if it is missing.
+fn crates_table(document: &mut DocumentMut) -> Result<&mut Table, Error> {
+    document
+        .entry("crates")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| Error::Config(String::from("crates is not a table").into()))
+}
+
+/// Checks that the edited config file `document` is still valid and that all of its' crates can
+/// be built, then writes it to `path`.
+fn write_document(path: &Path, document: &DocumentMut) -> Result<(), Error> {
+    let contents = document.to_string();
+    let config: ConfigFile = toml::from_str(&contents).map_err(|e| Error::Config(Box::new(e)))?;
+    sort_crates_into_buckets(config.crates.crates).map_err(Error::Config)?;
+    write_if_changed(path, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Sets the value of `item` to `value`, keeping the comments and whitespace around it.
+fn replace_value(item: &mut Item, value: impl Into<Value>) {
+    let mut value = value.into();
+    if let Some(existing) = item.as_value() {
+        *value.decor_mut() = existing.decor().clone();
+    }
+    *item = Item::Value(value);
+}
+
+/// The entry of the crate `name` in `crates`, if it is from crates.io and written as a table, like
+/// `name = { version = "<version>", package = "<name>" }`.
+fn crates_io_table<'a>(crates: &'a mut Table, name: &str) -> Option<&'a mut dyn TableLike> {
+    crates
+        .get_mut(name)?
+        .as_table_like_mut()
+        .filter(|table| !table.contains_key("git") && !table.contains_key("path"))
+}
+
+/// Pins the crate `name` in `crates` to `version` from crates.io. An existing entry written as a
+/// table keeps its' other keys, like `package`, and only gets its' version replaced.
+fn set_version(crates: &mut Table, name: &str, version: &str) {
+    match crates_io_table(crates, name) {
+        Some(table) => replace_value(table.entry("version").or_insert(Item::None), version),
+        None => replace_value(crates.entry(name).or_insert(Item::None), version),
+    }
+}
+
+/// Adds the crate `name` from `source` to the `[crates]` section of the config file at
+/// `config_path`, replacing an existing entry of the same name. Crates from crates.io are pinned
+/// to the newest version matching the requirement, see [set_version].
+pub(crate) fn add(config_path: &Path, name: &str, source: CrateSource) -> Result<(), Error> {
+    let mut document = read_document(config_path)?;
+    let crates = crates_table(&mut document)?;
+    if crates.contains_key(name) {
+        warn!("Replacing the existing entry of {name} in [crates]");
+    }
+    let value = match source {
+        CrateSource::CratesIo(requirement) => {
+            let package = crates_io_table(crates, name)
+                .and_then(|table| table.get("package"))
+                .and_then(Item::as_str)
+                .unwrap_or(name)
+                .to_owned();
+            if !is_valid_crate_name(&package) {
+                return Err(Error::Config(
+                    format!("{package:?} of {name} is not a valid crate name").into(),
+                ));
+            }
+            let version = newest_version(&package, &requirement)?;
+            info!("Adding {name} {version} from crates.io");
+            set_version(crates, name, &version.to_string());
+            return write_document(config_path, &document);
+        }
+        CrateSource::Git(url, reference) => {
+            info!("Adding {name} from {url}");
+            let mut table = InlineTable::new();
+            table.insert("git", url.into());
+            if let Some((key, reference)) = reference {
+                table.insert(key, reference.into());
+            }
+            Value::InlineTable(table)
+        }
+        CrateSource::Path(path) => {
+            info!("Adding {name} from {path}");
+            let mut table = InlineTable::new();
+            table.insert("path", path.into());
+            Value::InlineTable(table)
+        }
+    };
+    replace_value(crates.entry(name).or_insert(Item::None), value);
+    write_document(config_path, &document)
+}
+
+/// Removes the crate `name` from the `[crates]` section of the config file at `config_path`.
+pub(crate) fn remove(config_path: &Path, name: &str) -> Result<(), Error> {
+    let mut document = read_document(config_path)?;
+    if crates_table(&mut document)?.remove(name).is_none() {
+        error!("There is no crate named {name} in the [crates] section of {config_path:?}");
+        return Err(Error::Config(format!("{name} is not in [crates]").into()));
+    }
+    if document
+        .get("overrides")
+        .and_then(|overrides| overrides.get(name))
+        .is_some()
+    {
+        warn!("[overrides.{name}] is left in place");
+    }
+    write_document(config_path, &document)?;
+    info!("Removed {name}");
+    Ok(())
+}
+
+/// Bumps the crates.io crate `name`, or all crates.io crates if [None], in the `[crates]` section
+/// of the config file at `config_path` to the newest version matching their current version.
+/// Crates from git repositories or paths cannot be updated.
+pub(crate) fn update(config_path: &Path, name: Option<&str>) -> Result<(), Error> {
+    let mut document = read_document(config_path)?;
+    let crates = crates_table(&mut document)?;
+    if let Some(name) = name
+        && !crates.contains_key(name)
+    {
+        error!("There is no crate named {name} in the [crates] section of {config_path:?}");
+        return Err(Error::Config(format!("{name} is not in [crates]").into()));
+    }
+    let mut updated = 0usize;
+    for (key, item) in crates.iter_mut() {
+        if name.is_some_and(|name| name != key.get()) {
+            continue;
+        }
+        // Either `name = "<version>"` or `name = { version = "<version>", package = "<name>" }`
+        let (package, item) = match item.as_table_like_mut() {
+            Some(table) if table.contains_key("git") || table.contains_key("path") => match name {
+                Some(_) => {
+                    return Err(Error::Config(
+                        format!("{key} is not from crates.io and cannot be updated").into(),
+                    ));
+                }
+                None => continue,
+            },
+            Some(table) => {
+                let package = table
+                    .get("package")
+                    .and_then(Item::as_str)
+                    .unwrap_or(key.get())
+                    .to_owned();
+                match table.get_mut("version") {
+                    Some(version) => (package, version),
+                    None => continue,
+                }
+            }
+            None => (key.get().to_owned(), item),
+        };
+        let Some(current) = item.as_str().map(str::to_owned) else {
+            continue;
+        };
+        if !is_valid_crate_name(&package) {
+            return Err(Error::Config(
+                format!("{package:?} of {key} is not a valid crate name").into(),
+            ));
+        }
+        let requirement = VersionReq::parse(&current).map_err(|e| {
+            Error::Config(format!("invalid version {current:?} of {key}: {e}").into())
+        })?;
+        let newest = newest_version(&package, &requirement)?.to_string();
+        match newest == current {
+            true => debug!("{key} {current} is up to date"),
+            false => {
+                info!("Updating {key} {current} -> {newest}");
+                replace_value(item, newest);
+                updated += 1;
+            }
+        }
+    }
+    write_document(config_path, &document)?;
+    info!("Updated {updated} crate(s)");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use semver::VersionReq;
+    use toml_edit::DocumentMut;
+
+    use super::{
+        CrateSource, index_path, newest_matching, parse_source, remove, replace_value, set_version,
+        update,
+    };
+    use crate::error::Error;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn picks_newest_matching_version() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("rg"), "2/rg");
+        assert_eq!(index_path("bat"), "3/b/bat");
+        assert_eq!(index_path("RipGrep"), "ri/pg/ripgrep");
+        let index = [
+            r#"{"name":"ripgrep","vers":"13.0.0","yanked":false}"#,
+            r#"{"name":"ripgrep","vers":"14.0.0","yanked":false}"#,
+            r#"{"name":"ripgrep","vers":"14.1.1","yanked":false}"#,
+            r#"{"name":"ripgrep","vers":"14.2.0","yanked":true}"#,
+            r#"{"name":"ripgrep","vers":"15.0.0-beta.1","yanked":false}"#,
+        ]
+        .join("\n");
+        let newest = |requirement| {
+            newest_matching(&index, &VersionReq::parse(requirement).unwrap())
+                .map(|version| version.to_string())
+        };
+        assert_eq!(newest("14").as_deref(), Some("14.1.1"));
+        assert_eq!(newest("*").as_deref(), Some("14.1.1"));
+        assert_eq!(newest("13.0.0").as_deref(), Some("13.0.0"));
+        assert_eq!(newest("16"), None);
+    }
+
+    #[test]
+    fn parses_sources() {
+        assert_eq!(
+            parse_source(Some("ripgrep@14"), None, None, None).unwrap(),
+            (
+                String::from("ripgrep"),
+                CrateSource::CratesIo(VersionReq::parse("14").unwrap())
+            )
+        );
+        assert_eq!(
+            parse_source(None, Some("https://github.com/o/tool.git"), None, None)
+                .unwrap()
+                .0,
+            "tool"
+        );
+        assert!(parse_source(Some("tool@1"), Some("https://a/tool"), None, None).is_err());
+        assert!(parse_source(Some("wérkzeug"), None, None, None).is_err());
+    }
+
+    #[test]
+    fn keeps_formatting_of_edited_entries() {
+        let mut document = "[crates]\n# search\nripgrep = \"14.0.0\" # pinned\nexa = \"0.10.1\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        replace_value(&mut document["crates"]["ripgrep"], "14.1.1");
+        assert_eq!(
+            document.to_string(),
+            "[crates]\n# search\nripgrep = \"14.1.1\" # pinned\nexa = \"0.10.1\"\n"
+        );
+    }
+
+    #[test]
+    fn set_version_keeps_other_keys() {
+        let mut document = "[crates]\nrg = { version = \"14.0.0\", package = \"ripgrep\" }\nexa = \"0.10.1\"\nbat = { git = \"https://github.com/sharkdp/bat\" }\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+        let crates = document["crates"].as_table_mut().unwrap();
+        set_version(crates, "rg", "14.1.1");
+        set_version(crates, "exa", "0.10.2");
+        set_version(crates, "bat", "0.25.0");
+        set_version(crates, "fd", "10.2.0");
+        assert_eq!(
+            document.to_string(),
+            "[crates]\nrg = { version = \"14.1.1\", package = \"ripgrep\" }\nexa = \"0.10.2\"\nbat = \"0.25.0\"\nfd = \"10.2.0\"\n"
+        );
+    }
+
+    /// Writes a config file to `dir`, whose crates all come from git repositories or paths.
+    fn write_config(dir: &Path) -> PathBuf {
+        let tool = dir.join("tool");
+        std::fs::create_dir_all(&tool).unwrap();
+        std::fs::write(
+            tool.join("Cargo.toml"),
+            "[package]\nname = \"tool\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let config_path = dir.join("config.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                r#"# Crates to build
+[crates]
+# Search
+ripgrep = {{ git = "https://github.com/BurntSushi/ripgrep", tag = "14.1.1" }} # pinned
+# Local tool
+tool = {{ path = {tool:?} }}
+# Listing
+exa = {{ git = "https://github.com/ogham/exa" }}
+
+[options]
+workspace_path = "./warehouse/"
+verifying_key = ""
+autodelete_sources = false
+
+[dependencies]
+"#
+            ),
+        )
+        .unwrap();
+        config_path
+    }
+
+    #[test]
+    fn remove_keeps_surrounding_comments() {
+        let dir = TempDir::new("remove-test").unwrap();
+        let config_path = write_config(&dir);
+        let before = std::fs::read_to_string(&config_path).unwrap();
+        remove(&config_path, "tool").unwrap();
+        let tool_entry = before
+            .lines()
+            .find(|line| line.starts_with("tool = "))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config_path).unwrap(),
+            before.replace(&format!("# Local tool\n{tool_entry}\n"), "")
+        );
+        assert!(matches!(
+            remove(&config_path, "tool"),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn update_skips_git_and_path_entries() {
+        let dir = TempDir::new("update-skip-test").unwrap();
+        let config_path = write_config(&dir);
+        let before = std::fs::read_to_string(&config_path).unwrap();
+        // None of the crates come from crates.io, so nothing is looked up
+        update(&config_path, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), before);
+        for name in ["ripgrep", "tool"] {
+            match update(&config_path, Some(name)) {
+                Err(Error::Config(e)) => assert!(e.to_string().contains("not from crates.io")),
+                result => panic!("{name} was updated: {result:?}"),
+            }
+        }
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), before);
+    }
+
+    #[test]
+    fn rejects_invalid_package_names() {
+        let dir = TempDir::new("update-test").unwrap();
+        let config_path = dir.join("config.toml");
+        std::fs::write(
+            &config_path,
+            "[crates]\ntool = { version = \"1.0.0\", package = \"wérkzeug\" }\n",
+        )
+        .unwrap();
+        for name in [Some("tool"), None] {
+            match update(&config_path, name) {
+                Err(Error::Config(e)) => assert!(e.to_string().contains("not a valid crate name")),
+                result => panic!("invalid package name was looked up: {result:?}"),
+            }
+        }
+    }
+}
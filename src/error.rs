@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use crate::StdErrorS;
+use crate::process_crates::limits::LimitExceeded;
+
+/// Process exit codes of warehouseify. `0` means success and `2` is used by the argument parser
+/// for invalid command line usage. Every other code maps to exactly one [Error] variant.
+pub(crate) mod exit_code {
+    /// An unexpected I/O error occurred.
+    pub(crate) const IO: u8 = 1;
+    /// The configuration file or the command line arguments are invalid.
+    pub(crate) const CONFIG: u8 = 3;
+    /// A required program is missing on the host, or could not be installed.
+    pub(crate) const DEPENDENCY: u8 = 4;
+    /// Crate sources could not be downloaded.
+    pub(crate) const DOWNLOAD: u8 = 5;
+    /// Downloaded crate sources could not be extracted.
+    pub(crate) const EXTRACTION: u8 = 6;
+    /// A crate failed to compile.
+    pub(crate) const BUILD: u8 = 7;
+    /// A build exceeded its' timeout or resource limits.
+    pub(crate) const BUILD_LIMIT: u8 = 8;
+    /// A built binary could not be packaged into an archive.
+    pub(crate) const PACKAGING: u8 = 9;
+    /// The signing key could not be loaded, e.g. because of a wrong password.
+    pub(crate) const SIGNING_KEY: u8 = 10;
+    /// An archive could not be signed.
+    pub(crate) const SIGNING: u8 = 11;
+    /// With `--keep-going`, at least one crate could not be built, packaged or signed.
+    pub(crate) const CRATES_FAILED: u8 = 12;
+    /// An artifact could not be verified against its' signature or checksum.
+    pub(crate) const VERIFICATION: u8 = 13;
+    /// Artifacts could not be published.
+    pub(crate) const PUBLISH: u8 = 14;
+    /// A crate could not be installed with cargo-binstall, or the installed binary does not run.
+    pub(crate) const INSTALL_CHECK: u8 = 15;
+}
+
+#[derive(Debug)]
+/// Errors which abort a run of warehouseify, or the processing of a single crate. Each variant
+/// maps to a distinct process exit code, see [Error::exit_code].
+pub(crate) enum Error {
+    Config(StdErrorS),
+    Dependency(StdErrorS),
+    Download(StdErrorS),
+    Extraction(StdErrorS),
+    Build(StdErrorS),
+    BuildLimit(LimitExceeded),
+    Packaging(StdErrorS),
+    SigningKey(StdErrorS),
+    Signing(StdErrorS),
+    Verification(StdErrorS),
+    Publish(StdErrorS),
+    InstallCheck(StdErrorS),
+    Io(std::io::Error),
+    /// The number of crates which failed, when running with `--keep-going`.
+    CratesFailed(usize),
+}
+
+impl Error {
+    /// The process exit code warehouseify terminates with, if this error aborts the run.
+    #[must_use]
+    pub(crate) fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) => exit_code::CONFIG,
+            Error::Dependency(_) => exit_code::DEPENDENCY,
+            Error::Download(_) => exit_code::DOWNLOAD,
+            Error::Extraction(_) => exit_code::EXTRACTION,
+            Error::Build(_) => exit_code::BUILD,
+            Error::BuildLimit(_) => exit_code::BUILD_LIMIT,
+            Error::Packaging(_) => exit_code::PACKAGING,
+            Error::SigningKey(_) => exit_code::SIGNING_KEY,
+            Error::Signing(_) => exit_code::SIGNING,
+            Error::Verification(_) => exit_code::VERIFICATION,
+            Error::Publish(_) => exit_code::PUBLISH,
+            Error::InstallCheck(_) => exit_code::INSTALL_CHECK,
+            Error::Io(_) => exit_code::IO,
+            Error::CratesFailed(_) => exit_code::CRATES_FAILED,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "configuration error: {e}"),
+            Error::Dependency(e) => write!(f, "dependency error: {e}"),
+            Error::Download(e) => write!(f, "download error: {e}"),
+            Error::Extraction(e) => write!(f, "extraction error: {e}"),
+            Error::Build(e) => write!(f, "build error: {e}"),
+            Error::BuildLimit(e) => write!(f, "build error: {e}"),
+            Error::Packaging(e) => write!(f, "packaging error: {e}"),
+            Error::SigningKey(e) => write!(f, "signing key error: {e}"),
+            Error::Signing(e) => write!(f, "signing error: {e}"),
+            Error::Verification(e) => write!(f, "verification error: {e}"),
+            Error::Publish(e) => write!(f, "publish error: {e}"),
+            Error::InstallCheck(e) => write!(f, "install check error: {e}"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::CratesFailed(count) => write!(f, "{count} crate(s) failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e)
+            | Error::Dependency(e)
+            | Error::Download(e)
+            | Error::Extraction(e)
+            | Error::Build(e)
+            | Error::Packaging(e)
+            | Error::SigningKey(e)
+            | Error::Signing(e)
+            | Error::Verification(e)
+            | Error::Publish(e)
+            | Error::InstallCheck(e) => Some(e.as_ref()),
+            Error::BuildLimit(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::CratesFailed(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(value: LimitExceeded) -> Self {
+        Error::BuildLimit(value)
+    }
+}
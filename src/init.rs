@@ -0,0 +1,562 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use log::{debug, error, info};
+use minisign::KeyPair;
+use zeroize::Zeroizing;
+
+use crate::cli::Args;
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::artifact_dir;
+use crate::publish::{PublishConfig, write_if_changed};
+use crate::secrets::{SECRET_ENV, SECRET_PASSWORD_ENV, read_new_password};
+use crate::signatures::PUBLIC_ENV;
+
+/// Where `warehouseify init --github-actions` writes the workflow to.
+pub(crate) const WORKFLOW_PATH: &str = ".github/workflows/warehouse.yml";
+
+/// The GitHub-hosted runner building crates for `target`. warehouseify only builds for the host,
+/// so every target needs a runner of its' own, which must run Linux.
+#[must_use]
+fn runner(target: &str) -> Option<&'static str> {
+    match target {
+        "x86_64-unknown-linux-gnu" => Some("ubuntu-24.04"),
+        "aarch64-unknown-linux-gnu" => Some("ubuntu-24.04-arm"),
+        _ => None,
+    }
+}
+
+/// `value` as a single quoted YAML string.
+#[must_use]
+fn yaml_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The environment variables the publish job passes from the repository secrets to
+/// `warehouseify publish`, as `(variable, secret)`.
+#[must_use]
+fn publish_secrets(publish: &PublishConfig) -> Vec<(&str, &str)> {
+    match publish {
+        // The token of the workflow run may create releases in its' own repository
+        PublishConfig::Github(github) => vec![(github.token_env.as_str(), "GITHUB_TOKEN")],
+        PublishConfig::S3(s3) => [
+            &s3.access_key_id_env,
+            &s3.secret_access_key_env,
+            &s3.session_token_env,
+        ]
+        .into_iter()
+        .map(|name| (name.as_str(), name.as_str()))
+        .collect(),
+        PublishConfig::Static(_) => Vec::new(),
+    }
+}
+
+/// Renders a GitHub Actions workflow, which builds and signs all crates of the config file at
+/// `config_path` once per target in `targets`, each on a runner of that architecture, then merges
+/// the artifacts directories at `artifact_dir` of all builds and publishes them with `publish`.
+/// The signing key is read from the `WAREHOUSE_SECRET` repository secret and checked against the
+/// `WAREHOUSE_PUBLIC` repository variable.
+pub(crate) fn render_workflow(
+    config_path: &Path,
+    artifact_dir: &Path,
+    targets: &[String],
+    publish: &PublishConfig,
+) -> Result<String, Error> {
+    if targets.is_empty() {
+        return Err(Error::Config(
+            String::from("options.targets must name at least one target").into(),
+        ));
+    }
+    let mut matrix = String::new();
+    for target in targets {
+        let Some(runner) = runner(target) else {
+            error!(
+                "There is no GitHub-hosted runner to build {target} on. Supported targets are x86_64-unknown-linux-gnu and aarch64-unknown-linux-gnu."
+            );
+            return Err(Error::Config(
+                format!("unsupported target in options.targets: {target}").into(),
+            ));
+        };
+        write!(
+            matrix,
+            "\n          - target: {target}\n            runner: {runner}"
+        )
+        .expect("writing to a String cannot fail");
+    }
+    let mut publish_env = String::new();
+    for (variable, secret) in publish_secrets(publish) {
+        write!(
+            publish_env,
+            "\n          {variable}: ${{{{ secrets.{secret} }}}}"
+        )
+        .expect("writing to a String cannot fail");
+    }
+    let upload_published = match publish {
+        PublishConfig::Static(static_config) => format!(
+            r#"      - name: Upload the published directory
+        uses: actions/upload-artifact@v4
+        with:
+          name: warehouse
+          path: {}
+"#,
+            yaml_string(&static_config.path.to_string_lossy())
+        ),
+        _ => String::new(),
+    };
+    let config_path = yaml_string(&config_path.to_string_lossy());
+    let artifact_dir = yaml_string(&artifact_dir.to_string_lossy());
+    Ok(format!(
+        r#"# Generated by `warehouseify init --github-actions`.
+#
+# Builds and signs every crate in the config file on one runner per target in options.targets,
+# then publishes all archives from the default branch with the publisher configured in [publish].
+#
+# Requires the minisign secret key in the {SECRET_ENV} repository secret,
+# its' password in the {SECRET_PASSWORD_ENV} secret unless the key is unencrypted,
+# and the verifying key in the {PUBLIC_ENV} repository variable, which warehouseify
+# checks options.verifying_key against.
+name: warehouse
+
+on:
+  push:
+    paths:
+      - {config_path}
+  schedule:
+    - cron: "0 4 * * 1"
+  workflow_dispatch:
+
+permissions:
+  contents: write
+
+concurrency:
+  group: warehouse
+  cancel-in-progress: false
+
+env:
+  CARGO_TERM_COLOR: always
+  WAREHOUSE_CONFIG: {config_path}
+  {PUBLIC_ENV}: ${{{{ vars.{PUBLIC_ENV} }}}}
+
+jobs:
+  build:
+    name: Build for ${{{{ matrix.target }}}}
+    runs-on: ${{{{ matrix.runner }}}}
+    strategy:
+      fail-fast: false
+      matrix:
+        include:{matrix}
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install minisign
+        run: sudo apt-get update && sudo apt-get install --yes minisign
+      - name: Install warehouseify
+        run: cargo install --locked warehouseify
+      - name: Build and sign
+        env:
+          {SECRET_ENV}: ${{{{ secrets.{SECRET_ENV} }}}}
+          {SECRET_PASSWORD_ENV}: ${{{{ secrets.{SECRET_PASSWORD_ENV} }}}}
+        run: warehouseify --config "$WAREHOUSE_CONFIG" --no-confirm --keep-going
+      - name: Upload the artifacts directory
+        if: ${{{{ !cancelled() }}}}
+        uses: actions/upload-artifact@v4
+        with:
+          name: artifacts-${{{{ matrix.target }}}}
+          path: {artifact_dir}
+
+  publish:
+    needs: build
+    if: ${{{{ !cancelled() && github.ref == format('refs/heads/{{0}}', github.event.repository.default_branch) }}}}
+    runs-on: ubuntu-24.04
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install minisign
+        run: sudo apt-get update && sudo apt-get install --yes minisign
+      - name: Install warehouseify
+        run: cargo install --locked warehouseify
+      - name: Download the artifacts directories
+        uses: actions/download-artifact@v4
+        with:
+          pattern: artifacts-*
+          path: {artifact_dir}
+          merge-multiple: true
+      - name: Publish
+        env:
+          {SECRET_ENV}: ${{{{ secrets.{SECRET_ENV} }}}}
+          {SECRET_PASSWORD_ENV}: ${{{{ secrets.{SECRET_PASSWORD_ENV} }}}}{publish_env}
+        run: warehouseify --config "$WAREHOUSE_CONFIG" --no-confirm publish
+{upload_published}"#
+    ))
+}
+
+/// Writes a GitHub Actions workflow for the config file at `config_path` to [WORKFLOW_PATH], see
+/// [render_workflow]. An existing workflow is only overwritten after asking for confirmation,
+/// unless `no_confirm` is set.
+pub(crate) fn github_actions(
+    config: &ConfigFile,
+    config_path: &Path,
+    no_confirm: bool,
+) -> Result<(), Error> {
+    let Some(publish) = &config.publish else {
+        error!(
+            "The workflow publishes all archives, but no publisher is configured. Add a [publish] section to your config file first."
+        );
+        return Err(Error::Config(
+            String::from("no publisher configured in [publish]").into(),
+        ));
+    };
+    let workflow = render_workflow(
+        config_path,
+        &artifact_dir(config),
+        &config.options.targets,
+        publish,
+    )?;
+    let path = Path::new(WORKFLOW_PATH);
+    match std::fs::read_to_string(path) {
+        Ok(existing) if existing == workflow => {
+            info!("{WORKFLOW_PATH} is up to date");
+            return Ok(());
+        }
+        Ok(_) if !no_confirm => {
+            eprint!("{WORKFLOW_PATH} already exists. Overwrite it? [y/N] ");
+            if !crate::confirm()? {
+                debug!("Not overwriting {WORKFLOW_PATH}");
+                return Ok(());
+            }
+        }
+        _ => (),
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, workflow)?;
+    info!(
+        "Wrote {WORKFLOW_PATH}. Add the minisign secret key as the {SECRET_ENV} repository secret and the verifying key as the {PUBLIC_ENV} repository variable."
+    );
+    Ok(())
+}
+
+/// File name of the minisign secret key generated by `warehouseify init`, next to the config file.
+pub(crate) const KEY_FILE: &str = "warehouse.key";
+
+/// The answers to the questions asked by `warehouseify init`.
+#[derive(Debug)]
+struct Scaffold {
+    workspace_path: PathBuf,
+    targets: Vec<String>,
+    pkg_url: String,
+    cargo_auditable: bool,
+}
+
+/// Asks `question` on stderr and reads the answer from stdin. Returns `default` for an empty
+/// answer, or without asking at all if `no_confirm` is set.
+fn ask(question: &str, default: &str, no_confirm: bool) -> Result<String, Error> {
+    if no_confirm {
+        return Ok(default.to_owned());
+    }
+    match default.is_empty() {
+        true => eprint!("{question}: "),
+        false => eprint!("{question} [{default}]: "),
+    }
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    match answer.trim() {
+        "" => Ok(default.to_owned()),
+        answer => Ok(answer.to_owned()),
+    }
+}
+
+/// `value` as a TOML value, quoted and escaped as needed.
+#[must_use]
+fn toml_value(value: impl Into<toml::Value>) -> String {
+    value.into().to_string()
+}
+
+/// Renders a commented config file for `scaffold`, which signs with the key pair whose public key
+/// is `verifying_key` and whose secret key is stored at `key_path`.
+#[must_use]
+fn render_config(scaffold: &Scaffold, verifying_key: &str, key_path: &Path) -> String {
+    let pkg_url = match scaffold.pkg_url.is_empty() {
+        true => String::from(
+            "# pkg_url = \"https://warehouse.example.com/{ name }/{ version }/{ name }-{ target }-v{ version }.tar\"",
+        ),
+        false => format!("pkg_url = {}", toml_value(scaffold.pkg_url.as_str())),
+    };
+    format!(
+        r#"# Generated by `warehouseify init`. See the README for all options.
+
+# Crates to build and sign, in the same format as [dependencies] in a Cargo.toml.
+[crates]
+# ripgrep = "14.1.1"
+# tool = {{ git = "https://github.com/owner/tool", tag = "v1.0.0" }}
+# local-tool = {{ path = "./tool" }}
+
+[options]
+# Sources are built in build/ and signed archives are written to artifacts/ inside this directory.
+workspace_path = {workspace_path}
+# Targets the workflow written by `warehouseify init --github-actions` builds for, one runner each.
+targets = {targets}
+# Where cargo-binstall downloads archives from. Derived from [publish] instead, if that is set.
+{pkg_url}
+# The minisign key pair archives are signed with. Never commit the secret key, on CI runners pass
+# it in $WAREHOUSE_SECRET instead.
+verifying_key = {verifying_key}
+signing_key_file = {key_path}
+# Delete the sources of each crate once it has been built.
+autodelete_sources = false
+
+[dependencies]
+# Build crates with cargo-auditable, embedding their dependency tree into the binary.
+cargo-auditable = {{ enabled = {cargo_auditable}, version = "0.6.0" }}
+# Use sccache as RUSTC_WRAPPER when building crates.
+sccache = {{ enabled = false, version = "0.10.0" }}
+# After each run, install every crate built for the host with cargo-binstall and run it with --version.
+cargo-binstall = {{ enabled = false, version = "1.22.0" }}
+
+# Where `warehouseify publish` (or `warehouseify --publish`) uploads signed archives to. The
+# "s3" and "static" backends are described in the README.
+# [publish]
+# backend = "github"
+# repository = "owner/warehouse"
+"#,
+        workspace_path = toml_value(scaffold.workspace_path.to_string_lossy().as_ref()),
+        targets = toml_value(scaffold.targets.clone()),
+        verifying_key = toml_value(verifying_key),
+        key_path = toml_value(key_path.to_string_lossy().as_ref()),
+        cargo_auditable = scaffold.cargo_auditable,
+    )
+}
+
+/// `path` relative to the current directory, as an anchored `.gitignore` pattern, or [None] if it
+/// is not inside of the current directory.
+#[must_use]
+fn gitignore_pattern(path: &Path) -> Option<String> {
+    let mut pattern = String::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::Normal(name) => {
+                pattern.push('/');
+                pattern.push_str(&name.to_string_lossy());
+            }
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(pattern)
+}
+
+/// The `.gitignore` patterns for the build and artifacts directories in `workspace_path` and the
+/// secret key at `key_path`. Paths outside of the current directory are left out.
+#[must_use]
+fn gitignore_entries(workspace_path: &Path, key_path: &Path) -> Vec<String> {
+    [
+        gitignore_pattern(&workspace_path.join("build")).map(|pattern| pattern + "/"),
+        gitignore_pattern(&workspace_path.join("artifacts")).map(|pattern| pattern + "/"),
+        gitignore_pattern(key_path),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Appends those of `entries` to the `.gitignore` in the current directory which it does not
+/// contain yet.
+fn update_gitignore(entries: &[String]) -> Result<(), Error> {
+    let path = Path::new(".gitignore");
+    let mut contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::Io(e)),
+    };
+    let missing = entries
+        .iter()
+        .filter(|entry| !contents.lines().any(|line| line.trim() == entry.as_str()))
+        .cloned()
+        .collect::<Vec<String>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str("# warehouseify\n");
+    for entry in missing {
+        debug!("Adding {entry} to .gitignore");
+        contents.push_str(&entry);
+        contents.push('\n');
+    }
+    write_if_changed(path, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Sets up a new warehouse in the current directory: Asks for the workspace path, targets,
+/// `pkg-url` and whether to use cargo-auditable, unless they are given or `--no-confirm` is set,
+/// in which case the defaults are used. Then generates a minisign key pair, writes a commented
+/// config file to `config_path` and adds the workspace and the secret key to `.gitignore`.
+/// Refuses to overwrite an existing config file or secret key.
+pub(crate) fn init(
+    args: &Args,
+    config_path: &Path,
+    workspace_path: Option<&Path>,
+    targets: &[String],
+    pkg_url: Option<&str>,
+    cargo_auditable: Option<bool>,
+) -> Result<(), Error> {
+    let key_path = config_path.with_file_name(KEY_FILE);
+    for path in [config_path, key_path.as_path()] {
+        if path.exists() {
+            error!(
+                "{path:?} already exists. Remove it or pass a different path with --config to set up a new warehouse."
+            );
+            return Err(Error::Config(
+                format!("refusing to overwrite {path:?}").into(),
+            ));
+        }
+    }
+    let no_confirm = args.no_confirm;
+    let scaffold = Scaffold {
+        workspace_path: match workspace_path {
+            Some(path) => path.to_owned(),
+            None => ask("Workspace path", "./warehouse/", no_confirm)?.into(),
+        },
+        targets: match targets.is_empty() {
+            false => targets.to_vec(),
+            true => ask(
+                "Targets, separated by commas",
+                "x86_64-unknown-linux-gnu",
+                no_confirm,
+            )?
+            .split(',')
+            .map(|target| target.trim().to_owned())
+            .filter(|target| !target.is_empty())
+            .collect(),
+        },
+        pkg_url: match pkg_url {
+            Some(pkg_url) => pkg_url.to_owned(),
+            None => ask(
+                "URL cargo-binstall downloads archives from (pkg-url), empty to configure a publisher later",
+                "",
+                no_confirm,
+            )?,
+        },
+        cargo_auditable: match cargo_auditable {
+            Some(enabled) => enabled,
+            None => ask("Build crates with cargo-auditable?", "yes", no_confirm)?
+                .to_lowercase()
+                .starts_with('y'),
+        },
+    };
+    let password = read_new_password(args)?;
+    let keypair = KeyPair::generate_encrypted_keypair(Some(password.to_string()))
+        .map_err(|e| Error::SigningKey(Box::new(e)))?;
+    let secret_key = Zeroizing::new(
+        keypair
+            .sk
+            .to_box(None)
+            .map_err(|e| Error::SigningKey(Box::new(e)))?
+            .into_string(),
+    );
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&key_path)?
+        .write_all(secret_key.as_bytes())?;
+    info!("Wrote the encrypted minisign secret key to {key_path:?}");
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(config_path)?
+        .write_all(render_config(&scaffold, &keypair.pk.to_base64(), &key_path).as_bytes())?;
+    info!("Wrote {config_path:?}");
+    update_gitignore(&gitignore_entries(&scaffold.workspace_path, &key_path))?;
+    info!(
+        "Add the crates to build to the [crates] section of {config_path:?} and run warehouseify. To build on GitHub Actions, configure a publisher in [publish] and run `warehouseify init --github-actions`."
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{Scaffold, gitignore_entries, render_config, render_workflow};
+    use crate::config::ConfigFile;
+    use crate::publish::PublishConfig;
+
+    #[test]
+    fn renders_a_valid_config() {
+        let scaffold = Scaffold {
+            workspace_path: "./warehouse/".into(),
+            targets: vec![String::from("aarch64-unknown-linux-gnu")],
+            pkg_url: String::from("https://example.com/{ name }-\"{ version }\".tar"),
+            cargo_auditable: false,
+        };
+        let config: ConfigFile = toml::from_str(&render_config(
+            &scaffold,
+            "RWQkey",
+            Path::new("warehouse.key"),
+        ))
+        .unwrap();
+        assert_eq!(config.options.targets, scaffold.targets);
+        assert_eq!(config.options.pkg_url, scaffold.pkg_url);
+        assert_eq!(config.options.verifying_key, "RWQkey");
+        assert!(config.crates.crates.is_empty());
+        assert!(!config.dependencies.properties["cargo-auditable"].enabled);
+        assert_eq!(
+            gitignore_entries(&scaffold.workspace_path, Path::new("warehouse.key")),
+            [
+                "/warehouse/build/",
+                "/warehouse/artifacts/",
+                "/warehouse.key"
+            ]
+        );
+        assert_eq!(
+            gitignore_entries(Path::new("../warehouse"), Path::new("ci/warehouse.key")),
+            ["/ci/warehouse.key"]
+        );
+    }
+
+    #[test]
+    fn builds_one_job_per_target() {
+        let publish: PublishConfig =
+            toml::from_str("backend = \"github\"\nrepository = \"o/warehouse\"").unwrap();
+        let targets = [
+            String::from("x86_64-unknown-linux-gnu"),
+            String::from("aarch64-unknown-linux-gnu"),
+        ];
+        let workflow = render_workflow(
+            Path::new("config.toml"),
+            Path::new("./warehouse/artifacts/"),
+            &targets,
+            &publish,
+        )
+        .unwrap();
+        assert!(workflow.contains(
+            "          - target: x86_64-unknown-linux-gnu\n            runner: ubuntu-24.04\n"
+        ));
+        assert!(workflow.contains(
+            "          - target: aarch64-unknown-linux-gnu\n            runner: ubuntu-24.04-arm\n"
+        ));
+        assert!(workflow.contains("GITHUB_TOKEN: ${{ secrets.GITHUB_TOKEN }}"));
+        // Crates that failed to build under --keep-going must not keep the others from publishing
+        assert!(workflow.contains(
+            "  publish:\n    needs: build\n    if: ${{ !cancelled() && github.ref == format('refs/heads/{0}', github.event.repository.default_branch) }}\n"
+        ));
+        // Signing and publishing check options.verifying_key against the variable
+        assert!(workflow.contains("\nenv:\n  CARGO_TERM_COLOR: always\n  WAREHOUSE_CONFIG: 'config.toml'\n  WAREHOUSE_PUBLIC: ${{ vars.WAREHOUSE_PUBLIC }}\n"));
+        assert!(!workflow.contains("grep"));
+        assert!(workflow.contains("path: './warehouse/artifacts/'"));
+        assert!(
+            render_workflow(
+                Path::new("config.toml"),
+                Path::new("artifacts"),
+                &[String::from("x86_64-pc-windows-msvc")],
+                &publish,
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+use std::process::Command;
+
+use log::{debug, error, info, warn};
+
+use crate::config::ConfigFile;
+use crate::dependencies::{host_triple, rustc_version_verbose};
+use crate::error::Error;
+use crate::process_crates::artifact_dir;
+use crate::publish::{Artifact, collect_artifacts, default_layout, layout_pkg_url};
+use crate::serve::{manifest, newest_versions, start_local_server};
+use crate::temp_dir::TempDir;
+
+/// Returns `true` if `cargo-binstall` is a specified and enabled dependency within the config
+/// file, which turns on the install check.
+#[must_use]
+pub(crate) fn is_enabled(config: &ConfigFile) -> bool {
+    config
+        .dependencies
+        .properties
+        .get("cargo-binstall")
+        .is_some_and(|dep_props| dep_props.enabled)
+}
+
+/// The `cargo binstall` invocation installing `crate_spec` for `target` from the Cargo.toml at
+/// `manifest_path` into `root`. Only the binstall metadata of the manifest is used, neither
+/// quick-install nor compiling from source are allowed, and the archive must be signed. The local
+/// server only speaks plain HTTP, which cargo-binstall refuses unless `allow_insecure_http` is set.
+#[must_use]
+fn binstall_command(
+    manifest_path: &Path,
+    root: &Path,
+    target: &str,
+    crate_spec: &str,
+    allow_insecure_http: bool,
+) -> Command {
+    let mut command = Command::new("cargo");
+    command
+        .arg("binstall")
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .arg("--root")
+        .arg(root)
+        .args(["--targets", target])
+        .args(["--strategies", "crate-meta-data"])
+        .arg("--only-signed")
+        .arg("--no-confirm")
+        .arg("--no-track");
+    if allow_insecure_http {
+        command.arg("--allow-insecure-http");
+    }
+    command.arg(crate_spec);
+    command
+}
+
+/// Installs `artifact` with cargo-binstall from `pkg_url` into a temporary root, then runs the
+/// installed binary with `--version`.
+fn check_install(
+    config: &ConfigFile,
+    artifact: &Artifact,
+    pkg_url: &str,
+    work_dir: &Path,
+    allow_insecure_http: bool,
+) -> Result<(), Error> {
+    let crate_spec = format!("{}@{}", artifact.crate_name, artifact.version);
+    let crate_dir = work_dir.join(&artifact.crate_name);
+    std::fs::create_dir_all(crate_dir.join("src"))?;
+    std::fs::write(crate_dir.join("src/main.rs"), "fn main() {}\n")?;
+    let manifest_path = crate_dir.join("Cargo.toml");
+    std::fs::write(
+        &manifest_path,
+        manifest(artifact, pkg_url, &config.options.verifying_key)?,
+    )?;
+    let root = crate_dir.join("root");
+    info!("Installing {crate_spec} with cargo-binstall from {pkg_url}");
+    let output = binstall_command(
+        &manifest_path,
+        &root,
+        &artifact.target,
+        &crate_spec,
+        allow_insecure_http,
+    )
+    .output()?;
+    if !output.status.success() {
+        // cargo-binstall logs to stdout, errors end up in either of them
+        error!(
+            "cargo binstall {crate_spec} failed with {}:\n{}\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+        return Err(Error::InstallCheck(
+            format!("{crate_spec} could not be installed with cargo-binstall").into(),
+        ));
+    }
+    let binary = root.join("bin").join(&artifact.binary);
+    let output = Command::new(&binary).arg("--version").output()?;
+    match output.status.success() {
+        true => {
+            debug!(
+                "{binary:?} --version: {}",
+                String::from_utf8_lossy(&output.stdout).trim_end()
+            );
+            Ok(())
+        }
+        false => {
+            error!(
+                "Running the installed {binary:?} --version failed with {}:\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+            Err(Error::InstallCheck(
+                format!("the binary installed for {crate_spec} does not run").into(),
+            ))
+        }
+    }
+}
+
+/// Installs the most recent version of every crate in the artifacts directory with cargo-binstall
+/// and checks that the installed binary runs `--version`. If `published` is set, archives are
+/// downloaded from where the configured publisher uploaded them to, otherwise from a local server
+/// serving the artifacts directory over plain HTTP, which needs cargo-binstall 1.22.0 or newer.
+/// Only archives built for the host can be checked, all others are skipped.
+pub(crate) fn check_installs(config: &ConfigFile, published: bool) -> Result<(), Error> {
+    match Command::new("cargo").args(["binstall", "-V"]).output() {
+        Ok(output) if output.status.success() => (),
+        _ => {
+            error!(
+                "Could not execute `cargo binstall`. Is cargo-binstall installed? Enable it in the [dependencies] section of your config file and run warehouseify to install it."
+            );
+            return Err(Error::Dependency(
+                String::from("cargo-binstall is not available").into(),
+            ));
+        }
+    }
+    let rustc = rustc_version_verbose(None).map_err(Error::Dependency)?;
+    let host = host_triple(&rustc).ok_or_else(|| {
+        Error::Dependency(
+            String::from("Could not determine the host target from `rustc -vV`").into(),
+        )
+    })?;
+    let dir = artifact_dir(config);
+    let artifacts = collect_artifacts(&dir)?
+        .into_iter()
+        .filter(|artifact| artifact.target == host)
+        .collect::<Vec<Artifact>>();
+    if artifacts.is_empty() {
+        warn!(
+            "There are no archives built for the host {host} in {dir:?}, no installs were checked"
+        );
+        return Ok(());
+    }
+    let pkg_url = match published {
+        true => config.pkg_url(),
+        false => layout_pkg_url(&start_local_server(&dir, &artifacts)?, &default_layout()),
+    };
+    let work_dir = TempDir::new("install-check")?;
+    let mut failed = 0usize;
+    let newest = newest_versions(&artifacts);
+    for artifact in newest.iter() {
+        if let Err(e) = check_install(config, artifact, &pkg_url, &work_dir, !published) {
+            error!("Install check of {} failed: {e}", artifact.crate_name);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => {
+            info!(
+                "Installed {} crates with cargo-binstall successfully",
+                newest.len()
+            );
+            Ok(())
+        }
+        _ => Err(Error::InstallCheck(
+            format!("{failed} crate(s) could not be installed with cargo-binstall").into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::path::Path;
+
+    use super::binstall_command;
+
+    #[test]
+    fn installs_only_signed_archives_from_metadata() {
+        let command = binstall_command(
+            Path::new("/tmp/tool/Cargo.toml"),
+            Path::new("/tmp/tool/root"),
+            "x86_64-unknown-linux-gnu",
+            "tool@1.0.0",
+            false,
+        );
+        let args = command.get_args().collect::<Vec<&OsStr>>();
+        assert_eq!(args[0], "binstall");
+        assert!(args.contains(&OsStr::new("--only-signed")));
+        assert!(
+            args.windows(2)
+                .any(|window| window == ["--strategies", "crate-meta-data"])
+        );
+        assert!(!args.contains(&OsStr::new("--allow-insecure-http")));
+        assert_eq!(args.last(), Some(&OsStr::new("tool@1.0.0")));
+    }
+}
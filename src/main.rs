@@ -1,22 +1,40 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
-use std::process::{Command, exit};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 use std::sync::OnceLock;
+use std::time::{Instant, SystemTime};
 
 #[cfg(not(debug_assertions))]
 use clap::Parser;
-use cli::Args;
+use cli::{Args, Command as CliCommand};
 use config::ConfigFile;
-use dependencies::{Crate, list_missing_dependencies};
+use dependencies::{Crate, list_missing_dependencies, list_missing_toolchains};
+use error::Error;
 use log::*;
-use process_crates::{artifact_dir, build_dir, sort_crates_into_buckets, write_tar_to_build_dir};
+use output::{CrateOutcome, CrateResult, RunReport, describe_source, print_report};
+use process_crates::{
+    artifact_dir, build_dir, find_build_log, log_dir, sort_crates_into_buckets,
+    write_tar_to_build_dir,
+};
 use semver::VersionReq;
 
+pub(crate) mod checksums;
 pub(crate) mod cli;
 pub(crate) mod config;
 pub(crate) mod dependencies;
+pub(crate) mod edit_crates;
+pub(crate) mod error;
+pub(crate) mod init;
+pub(crate) mod install_check;
 pub(crate) mod output;
 pub(crate) mod process_crates;
+pub(crate) mod publish;
+pub(crate) mod rotate;
+pub(crate) mod secrets;
+pub(crate) mod serve;
+pub(crate) mod signatures;
+pub(crate) mod temp_dir;
+pub(crate) mod verify;
 
 static CLI_ARGUMENTS: OnceLock<Args> = OnceLock::new();
 /// `PathBuf` to the directory containing the sources of the crates to be built.
@@ -47,31 +65,53 @@ fn main() {
     )
 }
 
+/// Runs warehouseify and terminates with the exit code of the [Error] which aborted the run, if
+/// any. See [error::exit_code] for a list of all exit codes.
+#[cfg(target_os = "linux")]
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
 #[allow(clippy::expect_used)]
 #[cfg(target_os = "linux")]
-fn main() -> Result<(), StdErrorS> {
-    use minisign::SecretKeyBox;
-    use process_crates::{build_crate, dir_check_is_empty, sign_file};
-    use tar::Header;
+fn run() -> Result<(), Error> {
+    use process_crates::dir_check_is_empty;
+    use process_crates::sandbox::check_bubblewrap;
 
-    println!("Running warehousify");
+    let started = Instant::now();
+    let started_timestamp = iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string();
+    // Only the report is printed to stdout, so that it can be piped into other programs
+    eprintln!("Running warehousify");
 
     #[cfg(debug_assertions)]
     CLI_ARGUMENTS
         .set(Args {
             config: None,
             signing_key: None,
-            signing_key_password: String::from("Correct-Horse-Battery-Staple"),
+            signing_key_file: None,
+            signing_key_fd: None,
+            signing_key_password_fd: None,
+            signing_key_password: Some(String::from("Correct-Horse-Battery-Staple")),
             verbose: 4,
             no_confirm: false,
             locked: false,
             force: true,
             quiet: 0,
+            keep_going: false,
+            publish: false,
+            output: output::OutputFormat::Human,
+            command: None,
         })
         .expect("You messed up.");
     #[cfg(debug_assertions)]
     for _ in 0..10 {
-        println!(
+        eprintln!(
             "!!! This crate has been compiled in DEBUG mode and will use trace level logging and DEBUG-only behaviors. To disable this, compile and install the crate in release mode."
         )
     }
@@ -90,7 +130,7 @@ fn main() -> Result<(), StdErrorS> {
         1 => LevelFilter::Debug,
         2 => LevelFilter::Trace,
         _ => {
-            println!(
+            eprintln!(
                 r#"Woah there! You don't need to supply a bajillion "-v"'s. 2 is the limit! Interpreting input as "verbose"."#
             );
             LevelFilter::Trace
@@ -106,37 +146,103 @@ fn main() -> Result<(), StdErrorS> {
         2 => LevelFilter::Error,
         3 => LevelFilter::Off,
         _ => {
-            println!(
+            eprintln!(
                 r#"Woah there! You don't need to supply a bajillion "-q"'s. 3 is the limit! Interpreting input as "off""#
             );
             LevelFilter::Trace
         }
     };
-    env_logger::Builder::new()
+    if let Err(e) = env_logger::Builder::new()
         .filter(None, LevelFilter::Off)
         .filter(Some("warehouseify"), log_level)
-        .try_init()?;
+        .try_init()
+    {
+        eprintln!("Could not initialize logging: {e}");
+    }
     debug!("Hello, world!");
-    let config = ConfigFile::try_parse("config.toml".into())?;
-    if cli_arguments.signing_key.is_none() && config.options.signing_key.is_none() {
-        error!(
-            r#"You must supply a minisign signing key. Either set the "options.signing_key" variable in your configuration file, or provide it through the cli using the "--signing-key" flag."#
+    let config_path = cli_arguments
+        .config
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("config.toml"));
+    if let Some(CliCommand::Init {
+        github_actions: false,
+        workspace_path,
+        targets,
+        pkg_url,
+        cargo_auditable,
+    }) = &cli_arguments.command
+    {
+        return init::init(
+            cli_arguments,
+            &config_path,
+            workspace_path.as_deref(),
+            targets,
+            pkg_url.as_deref(),
+            *cargo_auditable,
         );
-        exit(1);
-    } else if let Some(secret) = &cli_arguments.signing_key {
-        SECRET
-            .set(
-                SecretKeyBox::from_string(secret)?
-                    .into_secret_key(Some(cli_arguments.signing_key_password.clone()))?,
-            )
-            .expect("Failed setting secret. Has it already been set?")
-    } else if let Some(secret) = &config.options.signing_key {
-        SECRET
-            .set(
-                SecretKeyBox::from_string(secret)?
-                    .into_secret_key(Some(cli_arguments.signing_key_password.clone()))?,
-            )
-            .expect("Failed setting secret. Has it already been set?")
+    }
+    let config = ConfigFile::try_parse(config_path.clone()).map_err(Error::Config)?;
+    if let Some(CliCommand::Verify { path }) = &cli_arguments.command {
+        return verify::verify(&config, path.as_deref());
+    }
+    if let Some(CliCommand::Serve { bind, port }) = &cli_arguments.command {
+        return serve::serve(&config, bind, *port);
+    }
+    match &cli_arguments.command {
+        Some(CliCommand::Add {
+            spec,
+            git,
+            tag,
+            branch,
+            rev,
+            path,
+        }) => {
+            let reference = match (tag, branch, rev) {
+                (Some(tag), _, _) => Some(("tag", tag.clone())),
+                (_, Some(branch), _) => Some(("branch", branch.clone())),
+                (_, _, Some(rev)) => Some(("rev", rev.clone())),
+                _ => None,
+            };
+            let (name, source) = edit_crates::parse_source(
+                spec.as_deref(),
+                git.as_deref(),
+                reference,
+                path.as_deref(),
+            )?;
+            return edit_crates::add(&config_path, &name, source);
+        }
+        Some(CliCommand::Remove { name }) => return edit_crates::remove(&config_path, name),
+        Some(CliCommand::Update { name }) => {
+            return edit_crates::update(&config_path, name.as_deref());
+        }
+        _ => (),
+    }
+    if let Some(CliCommand::Init { .. }) = &cli_arguments.command {
+        return init::github_actions(&config, &config_path, cli_arguments.no_confirm);
+    }
+    // Only the commands that sign or publish need to agree with the workflow on the key
+    signatures::check_public_env(&config)?;
+    SECRET
+        .set(secrets::load_signing_key(cli_arguments, &config.options)?)
+        .expect("Failed setting secret. Has it already been set?");
+    if let Some(CliCommand::RotateKey {
+        retired_signing_key_file,
+        retired_signing_key_password_fd,
+    }) = &cli_arguments.command
+    {
+        let retired_secret = secrets::load_retired_signing_key(
+            retired_signing_key_file,
+            *retired_signing_key_password_fd,
+            &config.options,
+        )?;
+        return rotate::rotate_key(&config, &retired_secret);
+    }
+    if let Some(CliCommand::Publish) = &cli_arguments.command {
+        publish::publish(&config)?;
+        if install_check::is_enabled(&config) {
+            install_check::check_installs(&config, true)?;
+        }
+        return Ok(());
     }
 
     PATH_SOURCES.set(config.options.workspace_path.join("build/")).expect("Fatal: PATH_SOURCES has been set before warehousify initialized it. Something is wrong");
@@ -144,30 +250,42 @@ fn main() -> Result<(), StdErrorS> {
     debug!("Config parsed successfully.");
     mkdirs(&config);
 
-    check_minisign();
+    check_minisign()?;
+    if config.options.sandbox {
+        check_bubblewrap().map_err(Error::Dependency)?;
+        if config
+            .dependencies
+            .properties
+            .get("sccache")
+            .is_some_and(|dep_props| dep_props.enabled)
+        {
+            warn!("sccache cannot be used for sandboxed builds and will be ignored.");
+        }
+    }
     trace!("Parsed config: {:#?}", &config);
-    let missing_dependencies = list_missing_dependencies(&config.dependencies)?;
+    let missing_dependencies =
+        list_missing_dependencies(&config.dependencies).map_err(Error::Dependency)?;
     if !cli_arguments.no_confirm && !missing_dependencies.is_empty() {
-        println!(
+        eprintln!(
             r#"The following dependencies have been determined to be missing on the host system: {}. Would you like to install them by using "cargo install"? [y/N]"#,
             fmt_missing_dependencies(&missing_dependencies)
         );
-        let mut buffer = String::new();
-        let stdin = std::io::stdin();
-        stdin.read_line(&mut buffer)?;
-        if buffer.trim().to_lowercase().starts_with('y') {
+        if confirm()? {
             install_missing_dependencies(
                 missing_dependencies
                     .iter()
                     .cloned()
                     .collect::<Vec<Crate>>()
                     .as_slice(),
-            )?;
+            )
+            .map_err(Error::Dependency)?;
         } else {
             error!(
                 "Cannot proceed without installing missing dependencies. Either manually install them or disable them in your configuration file."
             );
-            exit(1)
+            return Err(Error::Dependency(
+                String::from("missing dependencies were not installed").into(),
+            ));
         }
     } else if !missing_dependencies.is_empty() {
         install_missing_dependencies(
@@ -176,25 +294,55 @@ fn main() -> Result<(), StdErrorS> {
                 .cloned()
                 .collect::<Vec<Crate>>()
                 .as_slice(),
-        )?;
+        )
+        .map_err(Error::Dependency)?;
+    }
+    let missing_toolchains =
+        list_missing_toolchains(&config.toolchains()).map_err(Error::Dependency)?;
+    if !cli_arguments.no_confirm && !missing_toolchains.is_empty() {
+        eprintln!(
+            r#"The following Rust toolchains have been determined to be missing on the host system: {}. Would you like to install them by using "rustup toolchain install"? [y/N]"#,
+            missing_toolchains
+                .iter()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        if confirm()? {
+            install_missing_toolchains(&missing_toolchains).map_err(Error::Dependency)?;
+        } else {
+            error!(
+                "Cannot proceed without installing missing toolchains. Either manually install them or remove them from your configuration file."
+            );
+            return Err(Error::Dependency(
+                String::from("missing toolchains were not installed").into(),
+            ));
+        }
+    } else if !missing_toolchains.is_empty() {
+        install_missing_toolchains(&missing_toolchains).map_err(Error::Dependency)?;
     }
-    let sorted_crates = sort_crates_into_buckets(config.crates.crates.clone())?;
+    let sorted_crates =
+        sort_crates_into_buckets(config.crates.crates.clone()).map_err(Error::Config)?;
     let mut size = 0u128;
+    // SHA-256 digests of the downloaded crate sources, keyed by the name of the crate in the config
+    #[allow(unused_mut)]
+    let mut source_checksums = std::collections::HashMap::new();
     #[cfg(feature = "http-client")]
     {
         // TODO: This is broken?
         // BUG
         if !dir_check_is_empty(&config.options.workspace_path.join(path_sources())) {
-            panic!(
+            error!(
                 "The `workspace_path` specified in the config file contains a folder `build` which is not empty. Exiting for security reasons."
             );
+            return Err(Error::Config(
+                String::from("the build folder in `workspace_path` is not empty").into(),
+            ));
         }
         let downloaded_crates = crate::process_crates::download_sources(sorted_crates.clone())?;
         for item in downloaded_crates.into_iter() {
-            size = match size.checked_add(item.1.len() as u128) {
-                Some(v) => v,
-                None => u128::MAX,
-            };
+            size = size.saturating_add(item.1.len() as u128);
+            source_checksums.insert(item.0.clone(), checksums::sha256_hex(&item.1));
             write_tar_to_build_dir(item.1, &config.options.workspace_path.join(item.0))?;
         }
         debug!("Received {} kilobytes in crate source code", size / 1000);
@@ -214,7 +362,8 @@ fn main() -> Result<(), StdErrorS> {
     // At this point, we have all "remote" crates downloaded in the build directory
     // We can now edit the sources and compile them
 
-    let mut all_crate_paths = Vec::new();
+    // Path to each crate source, alongside a description of where it came from and its' checksum
+    let mut all_crate_paths: Vec<(PathBuf, String, Option<String>)> = Vec::new();
     for item in sorted_crates.locally_unavailable_crates.iter() {
         let crate_path = config
             .options
@@ -231,63 +380,188 @@ fn main() -> Result<(), StdErrorS> {
                     .filter(|path| path.is_dir())
                     .collect::<Vec<PathBuf>>();
                 debug!("Found subcrate entries: {additional_entries:?}",);
-                all_crate_paths.extend(additional_entries.into_iter());
+                all_crate_paths.extend(additional_entries.into_iter().map(|path| {
+                    (
+                        path,
+                        describe_source(&item.2),
+                        source_checksums.get(&item.0).cloned(),
+                    )
+                }));
             }
             Err(e) => debug!("Error: {e}"),
         };
     }
 
     for item in sorted_crates.locally_available_crates.iter() {
-        all_crate_paths.push(config.options.workspace_path.join(item.0.clone()));
-    }
-    for crate_path in all_crate_paths.iter() {
-        trace!("Modifying Cargo.toml of {crate_path:?}",);
-        process_crates::edit_sources::add_build_meta_info(crate_path, &config)?;
-    }
-    for crate_path in all_crate_paths.iter() {
-        trace!("Processing crate {crate_path:?} for building and signing");
-        let (binary_name, binary_bytes) = build_crate(&config, crate_path)?;
-        let mut tar_buf = Vec::with_capacity(binary_bytes.capacity());
-        match tar::Builder::new(&mut tar_buf).append_data(
-            &mut Header::new_gnu(),
-            &binary_name,
-            binary_bytes.as_slice(),
-        ) {
-            Ok(_) => debug!("{binary_name} executable added to tarball!"),
-            Err(e) => {
-                error!("Error occurred when building .tar file for {binary_name}: {e}");
-                panic!("Error when tarballing file");
-            }
-        };
-        match std::fs::write(path_binaries().join(format!("{binary_name}.tar")), &tar_buf) {
-            Ok(_) => debug!("Wrote {binary_name}.tar to disk!"),
-            Err(e) => {
-                error!("Could not write tar file for {binary_name} to disk: {e}");
-                panic!("I/O error");
-            }
-        };
-        let signature = match sign_file(&config, &tar_buf) {
-            Ok(sig) => sig,
-            Err(e) => {
-                error!("Error when trying to sign the tar archive for {binary_name}: {e}");
-                panic!("Signature error");
-            }
-        };
-        match std::fs::write(
-            path_binaries().join(format!("{binary_name}.tar.sig")),
-            &signature,
-        ) {
-            Ok(_) => debug!("Wrote {binary_name}.tar.sig to disk!"),
-            Err(e) => {
-                error!("Could not write signature file for {binary_name} archive to disk: {e}");
-                panic!("I/O error");
+        all_crate_paths.push((
+            config.options.workspace_path.join(item.0.clone()),
+            describe_source(&item.1),
+            None,
+        ));
+    }
+    let results = process_all(
+        &config,
+        all_crate_paths,
+        cli_arguments.keep_going,
+        process_crate,
+    )?;
+    let checksum_files = checksums::write_checksum_files(&config, path_binaries())?;
+    publish::index::write_index(&config, path_binaries())?;
+    let mut report = RunReport::new(started_timestamp, started.elapsed().as_secs_f64(), results);
+    report.checksum_files = checksum_files;
+    print_report(&report, cli_arguments.output)?;
+    check_report(&report)?;
+    if cli_arguments.publish {
+        publish::publish(&config)?;
+    }
+    if install_check::is_enabled(&config) {
+        install_check::check_installs(&config, cli_arguments.publish)?;
+    }
+
+    Ok(())
+}
+
+/// Processes each crate source in `crate_paths` with `process`, in order, and returns a
+/// [CrateResult] for each of them. `crate_paths` holds the path to each crate source, alongside a
+/// description of where it came from and its' checksum. If `keep_going` is set, a crate which
+/// fails is recorded as [CrateOutcome::Failed] and the remaining crates are still processed.
+/// Otherwise, the error of the first crate which fails is returned.
+fn process_all(
+    config: &ConfigFile,
+    crate_paths: Vec<(PathBuf, String, Option<String>)>,
+    keep_going: bool,
+    mut process: impl FnMut(&ConfigFile, &Path, &mut CrateResult) -> Result<CrateOutcome, Error>,
+) -> Result<Vec<CrateResult>, Error> {
+    let mut results = Vec::with_capacity(crate_paths.len());
+    for (crate_path, source, source_sha256) in crate_paths.into_iter() {
+        let crate_name = crate_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| crate_path.to_string_lossy().into_owned());
+        let mut result = CrateResult::new(crate_name, source, source_sha256);
+        let crate_started = Instant::now();
+        let processed = process(config, &crate_path, &mut result);
+        result.duration_secs = crate_started.elapsed().as_secs_f64();
+        match processed {
+            Ok(outcome) => result.outcome = outcome,
+            Err(e) if keep_going => {
+                error!(
+                    "Processing crate {} failed, continuing with the next crate: {e}",
+                    result.crate_name
+                );
+                result.outcome = CrateOutcome::Failed {
+                    reason: e.to_string(),
+                };
+                if result.log.is_none() {
+                    result.log = find_build_log(config, &crate_path);
+                }
             }
-        };
+            Err(e) => return Err(e),
+        }
+        results.push(result);
     }
+    Ok(results)
+}
 
+/// Returns [Error::CratesFailed], if any of the crates in `report` failed.
+fn check_report(report: &RunReport) -> Result<(), Error> {
+    if report.failed > 0 {
+        error!("Not all crates could be built and signed. See the summary above for details.");
+        return Err(Error::CratesFailed(report.failed));
+    }
     Ok(())
 }
 
+/// Edits the Cargo.toml of the crate at `crate_path`, builds it, packages the binary into a tar
+/// archive and signs that archive. The archive, its' signature and the build metadata are written
+/// to the artifacts directory. Fills in what is known about the crate into `result` along the way
+/// and returns the [CrateOutcome] of a successful run.
+fn process_crate(
+    config: &ConfigFile,
+    crate_path: &Path,
+    result: &mut CrateResult,
+) -> Result<CrateOutcome, Error> {
+    trace!("Modifying Cargo.toml of {crate_path:?}",);
+    process_crates::edit_sources::add_build_meta_info(crate_path, config)
+        .map_err(Error::Packaging)?;
+    trace!("Processing crate {crate_path:?} for building and signing");
+    let built_crate = process_crates::build_crate(config, crate_path)?;
+    result.package = Some(built_crate.metadata.crate_name.clone());
+    result.version = Some(built_crate.metadata.crate_version.clone());
+    result.target = Some(built_crate.metadata.target.clone());
+    result.log = Some(built_crate.log.clone());
+    let binary_name = &built_crate.name;
+    let binary_bytes = &built_crate.binary;
+    let mut tar_buf = Vec::with_capacity(binary_bytes.capacity());
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o755);
+    header.set_size(binary_bytes.len() as u64);
+    // cargo-binstall looks for the executable at the root of the archive, under its' own name
+    match tar::Builder::new(&mut tar_buf).append_data(
+        &mut header,
+        &built_crate.metadata.binary,
+        binary_bytes.as_slice(),
+    ) {
+        Ok(_) => debug!("{binary_name} executable added to tarball!"),
+        Err(e) => {
+            error!("Error occurred when building .tar file for {binary_name}: {e}");
+            return Err(Error::Packaging(Box::new(e)));
+        }
+    };
+    let archive = format!("{binary_name}.tar");
+    let archive_path = path_binaries().join(&archive);
+    match std::fs::write(&archive_path, &tar_buf) {
+        Ok(_) => debug!("Wrote {archive} to disk!"),
+        Err(e) => {
+            error!("Could not write tar file for {binary_name} to disk: {e}");
+            return Err(Error::Io(e));
+        }
+    };
+    let trusted_comment = signatures::render_trusted_comment(
+        config
+            .options
+            .trusted_comment
+            .as_deref()
+            .unwrap_or(signatures::DEFAULT_TRUSTED_COMMENT),
+        &signatures::TrustedCommentFields {
+            file: &archive,
+            crate_name: &built_crate.metadata.crate_name,
+            version: &built_crate.metadata.crate_version,
+            target: result.target.as_deref(),
+            timestamp: &built_crate.metadata.timestamp,
+            source_sha256: result.source_sha256.as_deref(),
+        },
+    )
+    .map_err(Error::Config)?;
+    let signature = match process_crates::sign_file(config, &tar_buf, Some(&trusted_comment)) {
+        Ok(sig) => sig,
+        Err(e) => {
+            error!("Error when trying to sign the tar archive for {binary_name}: {e}");
+            return Err(e);
+        }
+    };
+    let signature_path = path_binaries().join(format!("{archive}.sig"));
+    match std::fs::write(&signature_path, &signature) {
+        Ok(_) => debug!("Wrote {archive}.sig to disk!"),
+        Err(e) => {
+            error!("Could not write signature file for {binary_name} archive to disk: {e}");
+            return Err(Error::Io(e));
+        }
+    };
+    let metadata_path = path_binaries().join(format!("{binary_name}.build.toml"));
+    std::fs::write(
+        &metadata_path,
+        toml::to_string_pretty(&built_crate.metadata).map_err(|e| Error::Packaging(Box::new(e)))?,
+    )?;
+    debug!("Wrote {binary_name}.build.toml to disk!");
+    Ok(CrateOutcome::Built {
+        archive: archive_path,
+        archive_sha256: checksums::sha256_hex(&tar_buf),
+        signature: signature_path,
+        metadata: metadata_path,
+    })
+}
+
 #[allow(clippy::expect_used)]
 /// Takes in a list of [Crate]s and tries to install them on the host with `cargo install`.
 /// Will panic the program if CLI args cannot be found. Will return an error, if the specified dependencies
@@ -330,6 +604,35 @@ fn install_missing_dependencies(deps: &[Crate]) -> Result<(), StdErrorS> {
     }
 }
 
+/// Takes in a list of Rust toolchains and tries to install them on the host with
+/// `rustup toolchain install`, using the minimal profile. Will error, if `rustup` returns an error
+/// or if the command invocation fails altogether.
+fn install_missing_toolchains(toolchains: &HashSet<String>) -> Result<(), StdErrorS> {
+    let install_result = Command::new("rustup")
+        .arg("toolchain")
+        .arg("install")
+        .arg("--profile")
+        .arg("minimal")
+        .args(toolchains)
+        .spawn()?
+        .wait()?;
+    log::debug!("{install_result:?}");
+    match install_result.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "the installation of toolchains failed; cannot continue, rustup exited with {install_result}"
+        )
+        .into()),
+    }
+}
+
+/// Reads a line from stdin and returns `true`, if the user answered with "y" or "yes".
+fn confirm() -> std::io::Result<bool> {
+    let mut buffer = String::new();
+    std::io::stdin().read_line(&mut buffer)?;
+    Ok(buffer.trim().to_lowercase().starts_with('y'))
+}
+
 #[allow(clippy::arithmetic_side_effects)]
 /// Basically, a [std::fmt::Display] for `HashSet<Crate>` ordered on wish.com. I can't impl
 /// Display for HashSet<Crate> unless I make it a newtype, and I don't want to deal with that.
@@ -350,22 +653,26 @@ fn fmt_missing_dependencies(deps: &HashSet<Crate>) -> String {
     missing
 }
 
-/// Panics, if `minisign --help` cannot be executed successfully (with an exit status code of `2` (exit code returned when invoking --help)).
-fn check_minisign() {
+/// Errors, if `minisign --help` cannot be executed successfully (with an exit status code of `2` (exit code returned when invoking --help)).
+fn check_minisign() -> Result<(), Error> {
     match Command::new("minisign").arg("--help").output() {
         Ok(output) => match output.status.code().is_some_and(|code| code == 2) {
-            true => (),
+            true => Ok(()),
             false => {
                 log::error!(
                     "Executing minisign failed: Exit code {}. Is minisign installed and available on your $PATH?",
                     output.status
                 );
-                panic!("Could not execute minisign successfully")
+                Err(Error::Dependency(
+                    String::from("Could not execute minisign successfully").into(),
+                ))
             }
         },
         Err(e) => {
             log::error!("Executing minisign failed: {e}");
-            panic!("Could not execute minisign successfully")
+            Err(Error::Dependency(
+                String::from("Could not execute minisign successfully").into(),
+            ))
         }
     }
 }
@@ -379,4 +686,83 @@ fn mkdirs(config: &ConfigFile) {
         Ok(_) => (),
         Err(debug) => debug!("mkdirs: {debug}"),
     }
+    match std::fs::create_dir_all(log_dir(config)) {
+        Ok(_) => (),
+        Err(debug) => debug!("mkdirs: {debug}"),
+    }
+    if let Some(target_dir) = &config.options.target_dir {
+        match std::fs::create_dir_all(target_dir) {
+            Ok(_) => (),
+            Err(debug) => debug!("mkdirs: {debug}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use crate::config::ConfigFile;
+    use crate::error::Error;
+    use crate::output::{CrateOutcome, CrateResult, RunReport};
+    use crate::{check_report, process_all};
+
+    /// Processes the crates `a`, `b` and `c`, of which `b` fails, and returns the names of the
+    /// crates which were processed alongside the result.
+    fn process_failing_crate(keep_going: bool) -> (Vec<String>, Result<Vec<CrateResult>, Error>) {
+        let config: ConfigFile = toml::from_str(
+            "[crates]\n[options]\nworkspace_path = \"/nonexistent\"\nverifying_key = \"\"\nautodelete_sources = false\n[dependencies]\n",
+        )
+        .unwrap();
+        let crate_paths = ["a", "b", "c"]
+            .map(|name| {
+                (
+                    PathBuf::from("/nonexistent/build").join(name),
+                    String::from("crates.io"),
+                    None,
+                )
+            })
+            .to_vec();
+        let mut processed = Vec::new();
+        let results = process_all(
+            &config,
+            crate_paths,
+            keep_going,
+            |_, crate_path: &Path, result: &mut CrateResult| {
+                processed.push(result.crate_name.clone());
+                match crate_path.ends_with("b") {
+                    true => Err(Error::Build(String::from("does not compile").into())),
+                    false => Ok(CrateOutcome::Built {
+                        archive: crate_path.join("archive.tar"),
+                        archive_sha256: String::new(),
+                        signature: crate_path.join("archive.tar.sig"),
+                        metadata: crate_path.join("archive.build.toml"),
+                    }),
+                }
+            },
+        );
+        (processed, results)
+    }
+
+    #[test]
+    fn keeps_going_after_failed_crate() {
+        let (processed, results) = process_failing_crate(true);
+        assert_eq!(processed, ["a", "b", "c"]);
+        let results = results.unwrap();
+        assert!(!results[0].outcome.is_failed());
+        match &results[1].outcome {
+            CrateOutcome::Failed { reason } => assert_eq!(reason, "build error: does not compile"),
+            outcome => panic!("failed crate was not recorded: {outcome:?}"),
+        }
+        assert!(!results[2].outcome.is_failed());
+        let report = RunReport::new(String::new(), 0.0, results);
+        assert!(matches!(check_report(&report), Err(Error::CratesFailed(1))));
+    }
+
+    #[test]
+    fn stops_at_failed_crate() {
+        let (processed, results) = process_failing_crate(false);
+        assert_eq!(processed, ["a", "b"]);
+        assert!(matches!(results, Err(Error::Build(_))));
+    }
 }
@@ -0,0 +1,251 @@
+use std::path::PathBuf;
+
+use cargo_toml::Dependency;
+use serde::Serialize;
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+/// How the result of a run is reported on stdout.
+pub(crate) enum OutputFormat {
+    /// A human readable summary table.
+    #[default]
+    Human,
+    /// A machine readable [RunReport], serialized as JSON.
+    Json,
+    /// A machine readable [RunReport], serialized as TOML.
+    Toml,
+}
+
+/// What happened to a single crate during a run.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum CrateOutcome {
+    /// The crate was built and its' archive signed.
+    Built {
+        /// Path to the signed archive in the artifacts directory.
+        archive: PathBuf,
+        /// SHA-256 digest of the archive.
+        archive_sha256: String,
+        /// Path to the minisign signature of the archive.
+        signature: PathBuf,
+        /// Path to the build metadata of the archive.
+        metadata: PathBuf,
+    },
+    /// The crate could not be built, packaged or signed.
+    Failed {
+        /// Why processing the crate failed.
+        reason: String,
+    },
+}
+
+impl CrateOutcome {
+    #[must_use]
+    pub(crate) fn is_failed(&self) -> bool {
+        matches!(self, CrateOutcome::Failed { .. })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CrateResult {
+    /// Name of the crate source directory.
+    pub(crate) crate_name: String,
+    /// Where the crate source came from, e.g. `crates.io` or `git+https://...?tag=v1`.
+    pub(crate) source: String,
+    /// SHA-256 digest of the downloaded (and decompressed) source archive, if it was downloaded.
+    pub(crate) source_sha256: Option<String>,
+    /// Package name of the crate, once its' manifest has been read.
+    pub(crate) package: Option<String>,
+    /// Package version of the crate, once it has been built.
+    pub(crate) version: Option<String>,
+    /// The target triple the crate has been built for.
+    pub(crate) target: Option<String>,
+    #[serde(flatten)]
+    pub(crate) outcome: CrateOutcome,
+    /// Path to the full build log of the crate, if it got as far as being built.
+    pub(crate) log: Option<PathBuf>,
+    /// How long processing the crate took, in seconds.
+    pub(crate) duration_secs: f64,
+}
+
+impl CrateResult {
+    /// Creates the result for a crate which has not been processed yet. Its' outcome is
+    /// [CrateOutcome::Failed], until processing the crate finishes.
+    #[must_use]
+    pub(crate) fn new(crate_name: String, source: String, source_sha256: Option<String>) -> Self {
+        Self {
+            crate_name,
+            source,
+            source_sha256,
+            package: None,
+            version: None,
+            target: None,
+            outcome: CrateOutcome::Failed {
+                reason: String::from("processing did not finish"),
+            },
+            log: None,
+            duration_secs: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Machine readable report of an entire run, printed with `--output json` or `--output toml`.
+pub(crate) struct RunReport {
+    /// ISO 8601 timestamp of when the run started.
+    pub(crate) started: String,
+    /// How long the entire run took, in seconds.
+    pub(crate) duration_secs: f64,
+    /// Number of crates which were built and signed.
+    pub(crate) succeeded: usize,
+    /// Number of crates which failed.
+    pub(crate) failed: usize,
+    pub(crate) crates: Vec<CrateResult>,
+    /// Paths to the signed checksum files covering all archives in the artifacts directory.
+    pub(crate) checksum_files: Vec<PathBuf>,
+}
+
+impl RunReport {
+    #[must_use]
+    pub(crate) fn new(started: String, duration_secs: f64, crates: Vec<CrateResult>) -> Self {
+        let failed = crates
+            .iter()
+            .filter(|result| result.outcome.is_failed())
+            .count();
+        Self {
+            started,
+            duration_secs,
+            succeeded: crates.len().saturating_sub(failed),
+            failed,
+            crates,
+            checksum_files: Vec::new(),
+        }
+    }
+}
+
+/// Describes where a crate from the `[crates]` section of the config file comes from, in the
+/// style of cargo's source ids: `crates.io`, `git+<url>[?tag=|?branch=|?rev=<ref>]` or
+/// `path+<path>`.
+#[must_use]
+pub(crate) fn describe_source(dependency: &Dependency) -> String {
+    let Some(detail) = dependency.detail() else {
+        return String::from("crates.io");
+    };
+    if let Some(git) = &detail.git {
+        let reference = [
+            ("rev", &detail.rev),
+            ("tag", &detail.tag),
+            ("branch", &detail.branch),
+        ]
+        .into_iter()
+        .find_map(|(kind, value)| value.as_ref().map(|value| format!("?{kind}={value}")))
+        .unwrap_or_default();
+        format!("git+{git}{reference}")
+    } else if let Some(path) = &detail.path {
+        format!("path+{path}")
+    } else {
+        String::from("crates.io")
+    }
+}
+
+/// Prints the result of a run to stdout, in the given [OutputFormat].
+pub(crate) fn print_report(report: &RunReport, format: OutputFormat) -> Result<(), Error> {
+    match format {
+        OutputFormat::Human => print_summary(&report.crates),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(report).map_err(std::io::Error::from)?
+        ),
+        OutputFormat::Toml => print!(
+            "{}",
+            toml::to_string_pretty(report).map_err(std::io::Error::other)?
+        ),
+    }
+    Ok(())
+}
+
+/// Prints a table with one row per crate, stating whether it was built and signed successfully
+/// or why it failed, and where its' build log can be found, to stdout.
+pub(crate) fn print_summary(results: &[CrateResult]) {
+    let name_width = results
+        .iter()
+        .map(|result| result.crate_name.len())
+        .chain(std::iter::once("CRATE".len()))
+        .max()
+        .unwrap_or_default();
+    println!();
+    println!("{:<name_width$}  {:<6}  DETAILS", "CRATE", "STATUS");
+    for result in results {
+        let (status, details) = match &result.outcome {
+            CrateOutcome::Built { archive, .. } => ("ok", archive.to_string_lossy()),
+            // Only the first line, to keep the table readable. The full error has been logged.
+            CrateOutcome::Failed { reason } => {
+                ("FAILED", reason.lines().next().unwrap_or_default().into())
+            }
+        };
+        println!("{:<name_width$}  {status:<6}  {details}", result.crate_name);
+        if let Some(log) = &result.log {
+            println!("{:<name_width$}  {:<6}  log: {}", "", "", log.display());
+        }
+    }
+    let failed = results
+        .iter()
+        .filter(|result| result.outcome.is_failed())
+        .count();
+    println!(
+        "\n{} of {} crates built and signed, {failed} failed.",
+        results.len().saturating_sub(failed),
+        results.len()
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use cargo_toml::Dependency;
+
+    use super::{CrateOutcome, CrateResult, RunReport, describe_source};
+
+    #[test]
+    fn describes_sources() {
+        let crates: toml::Table = toml::from_str(
+            r#"
+            simple = "1.0.0"
+            tagged = { git = "https://github.com/o/r", tag = "v1" }
+            local = { path = "./tool" }
+            "#,
+        )
+        .unwrap();
+        let describe =
+            |name: &str| describe_source(&crates[name].clone().try_into::<Dependency>().unwrap());
+        assert_eq!(describe("simple"), "crates.io");
+        assert_eq!(describe("tagged"), "git+https://github.com/o/r?tag=v1");
+        assert_eq!(describe("local"), "path+./tool");
+    }
+
+    #[test]
+    fn report_serializes_to_json_and_toml() {
+        let mut built =
+            CrateResult::new(String::from("tool-1.0.0"), String::from("crates.io"), None);
+        built.outcome = CrateOutcome::Built {
+            archive: PathBuf::from("artifacts/tool.tar"),
+            archive_sha256: String::from("00"),
+            signature: PathBuf::from("artifacts/tool.tar.sig"),
+            metadata: PathBuf::from("artifacts/tool.build.toml"),
+        };
+        let failed = CrateResult::new(String::from("other-2.0.0"), String::from("crates.io"), None);
+        let report = RunReport::new(
+            String::from("2025-01-01T00:00:00Z"),
+            1.5,
+            vec![built, failed],
+        );
+        assert_eq!((report.succeeded, report.failed), (1, 1));
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["crates"][0]["status"], "built");
+        assert_eq!(json["crates"][0]["archive_sha256"], "00");
+        assert_eq!(json["crates"][1]["status"], "failed");
+        let toml = toml::to_string_pretty(&report).unwrap();
+        assert!(toml.contains(r#"status = "failed""#));
+    }
+}
@@ -1,14 +1,21 @@
-use std::path::Path;
-use std::process::Command;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
 use std::time::SystemTime;
 
-use build_command::create_build_command;
+use build_command::{PASSTHROUGH_ENV, build_env, create_build_command, create_vendor_command};
+use cargo_messages::{find_executables, render_stdout, select_executable};
 use cargo_toml::Manifest;
 use log::{debug, error, info, trace, warn};
 use minisign::PublicKey;
+use serde::{Deserialize, Serialize};
 
-use crate::process_crates::panic_on_dangerous_path;
-use crate::{ConfigFile, SECRET, StdErrorS};
+use crate::dependencies::{host_triple, rustc_version_verbose};
+use crate::error::Error;
+use crate::process_crates::limits::output_with_limits;
+use crate::process_crates::sandbox::{VENDOR_CONFIG, VENDOR_DIR, sandboxed, write_vendor_config};
+use crate::process_crates::{check_dangerous_path, log_dir};
+use crate::{ConfigFile, SECRET};
 
 // TODO
 // BUG
@@ -18,10 +25,22 @@ use crate::{ConfigFile, SECRET, StdErrorS};
 // we should have created.
 
 mod build_command {
+    use std::collections::BTreeMap;
+
     use log::trace;
 
     use super::*;
 
+    /// Host environment variables passed through to builds, unless `options.inherit_env` is set.
+    pub(super) const PASSTHROUGH_ENV: [&str; 6] = [
+        "PATH",
+        "HOME",
+        "CARGO_HOME",
+        "RUSTUP_HOME",
+        "TMPDIR",
+        "USER",
+    ];
+
     /// Returns `true` if `cargo-auditable` is a specified and enabled dependency within the config file.
     #[must_use]
     fn check_auditable(config: &ConfigFile) -> bool {
@@ -32,16 +51,88 @@ mod build_command {
             .is_some_and(|dep_props| dep_props.enabled)
     }
 
-    /// Create the build command for a given crate.
+    /// Returns `true` if `sccache` is a specified and enabled dependency within the config file.
+    #[must_use]
+    fn check_sccache(config: &ConfigFile) -> bool {
+        config
+            .dependencies
+            .properties
+            .get("sccache")
+            .is_some_and(|dep_props| dep_props.enabled)
+    }
+
+    /// The environment variables warehouseify sets when building a given crate. Does not include
+    /// variables passed through from the host.
+    #[must_use]
+    pub(super) fn build_env(config: &ConfigFile, crate_name: &str) -> BTreeMap<String, String> {
+        let mut env = config.env(crate_name);
+        if let Some(target_dir) = &config.options.target_dir {
+            let target_dir = std::path::absolute(target_dir).unwrap_or(target_dir.clone());
+            env.insert(
+                String::from("CARGO_TARGET_DIR"),
+                target_dir.to_string_lossy().into_owned(),
+            );
+        }
+        if check_sccache(config) && !config.options.sandbox {
+            env.insert(String::from("RUSTC_WRAPPER"), String::from("sccache"));
+        }
+        env
+    }
+
+    /// Create a `cargo` command for a given crate, with the crates' toolchain and build
+    /// environment applied. Subcommands and arguments are left to the caller.
+    #[must_use]
+    fn create_cargo_command(config: &ConfigFile, crate_name: &str) -> Command {
+        let toolchain = config.toolchain(crate_name);
+        let env = build_env(config, crate_name);
+        let crate_name = crate_name.trim().to_lowercase();
+        let mut base_cmd = Command::new("cargo");
+        if let Some(toolchain) = toolchain {
+            trace!("Using toolchain {toolchain} for {crate_name}");
+            base_cmd.arg(format!("+{toolchain}"));
+        }
+        if !config.options.inherit_env {
+            trace!("Clearing host environment for {crate_name}");
+            base_cmd.env_clear();
+            for var in PASSTHROUGH_ENV {
+                if let Some(value) = std::env::var_os(var) {
+                    base_cmd.env(var, value);
+                }
+            }
+        }
+        trace!("Build environment for {crate_name}: {env:?}");
+        base_cmd.envs(env);
+        base_cmd
+    }
+
+    /// Create the command vendoring all dependencies of a given crate into
+    /// `<crate_path>/vendor`, so that it can be built offline.
+    #[must_use]
+    pub(super) fn create_vendor_command(
+        config: &ConfigFile,
+        crate_path: &Path,
+        crate_name: &str,
+    ) -> Command {
+        trace!("Creating vendor command for {crate_name}");
+        let mut base_cmd = create_cargo_command(config, crate_name);
+        base_cmd
+            .arg("vendor")
+            .arg("--manifest-path")
+            .arg(crate_path.join("Cargo.toml"))
+            .arg(crate_path.join(VENDOR_DIR));
+        base_cmd
+    }
+
+    /// Create the build command for a given crate. If `options.sandbox` is set, the build runs
+    /// offline, against the vendored dependencies, inside of a sandbox.
     #[must_use]
     pub(super) fn create_build_command(
         config: &ConfigFile,
         crate_path: &Path,
         crate_name: &str,
     ) -> Command {
-        let crate_name = crate_name.trim().to_lowercase();
         trace!("Creating build command for {crate_name}");
-        let mut base_cmd = Command::new("cargo");
+        let mut base_cmd = create_cargo_command(config, crate_name);
         if check_auditable(config) {
             base_cmd.arg("auditable");
         };
@@ -49,43 +140,229 @@ mod build_command {
             .arg("build")
             .arg("--manifest-path")
             .arg(crate_path.join("Cargo.toml"))
-            .arg("--release");
+            .arg("--release")
+            .arg("--message-format=json-render-diagnostics");
+        if !config.options.sandbox {
+            return base_cmd;
+        }
         base_cmd
+            .arg("--offline")
+            .arg("--config")
+            .arg(crate_path.join(VENDOR_CONFIG));
+        let mut writable_paths = vec![crate_path.to_path_buf()];
+        if let Some(target_dir) = &config.options.target_dir {
+            writable_paths.push(target_dir.clone());
+        }
+        sandboxed(&base_cmd, &writable_paths, !config.options.inherit_env)
     }
 }
 
-/// Sign all binaries created in the output dir specified in the [ConfigFile]. Will error if any
-/// errors occur during signing.
-pub(crate) fn sign_file(config: &ConfigFile, file: &[u8]) -> Result<Vec<u8>, StdErrorS> {
-    crate::check_minisign();
-    Ok(minisign::sign(
-        Some(
-            &match PublicKey::from_base64(config.options.verifying_key.as_str()) {
-                Ok(key) => key,
-                Err(e) => {
-                    error!(
-                        "The public/verifying key provided in the config file is not valid Base64: {e}"
-                    );
-                    panic!("Malformed public key");
+mod cargo_messages {
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+
+    /// A single line of output of `cargo build --message-format=json`. Only the fields we care
+    /// about are deserialized; all other messages and fields are ignored.
+    #[derive(Debug, Deserialize)]
+    struct CargoMessage {
+        reason: String,
+        target: Option<CargoTarget>,
+        executable: Option<PathBuf>,
+        #[serde(default)]
+        filenames: Vec<PathBuf>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct CargoTarget {
+        name: String,
+        kind: Vec<String>,
+    }
+
+    /// Collects all `(target name, executable path)` pairs from the `compiler-artifact` messages
+    /// cargo printed to stdout. Lines which are not valid JSON are skipped.
+    #[must_use]
+    pub(super) fn find_executables(stdout: &[u8]) -> Vec<(String, PathBuf)> {
+        String::from_utf8_lossy(stdout)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+            .filter(|message| message.reason == "compiler-artifact")
+            .filter_map(|message| match (message.target, message.executable) {
+                (Some(target), Some(executable)) if target.kind.iter().any(|k| k == "bin") => {
+                    Some((target.name, executable))
                 }
-            },
-        ),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Renders what cargo printed to stdout for the build log: Each `compiler-artifact` message
+    /// becomes a line listing the files produced for its' target, all other messages are dropped.
+    /// Lines which are not valid JSON, like the output of `cargo vendor`, are kept as they are.
+    #[must_use]
+    pub(super) fn render_stdout(stdout: &[u8]) -> String {
+        let mut rendered = String::new();
+        for line in String::from_utf8_lossy(stdout).lines() {
+            match serde_json::from_str::<CargoMessage>(line) {
+                Ok(CargoMessage {
+                    reason,
+                    target: Some(target),
+                    filenames,
+                    ..
+                }) if reason == "compiler-artifact" => {
+                    let filenames = filenames
+                        .iter()
+                        .map(|path| path.to_string_lossy())
+                        .collect::<Vec<_>>();
+                    rendered.push_str(&format!(
+                        "artifact {} ({}): {}\n",
+                        target.name,
+                        target.kind.join(", "),
+                        filenames.join(" ")
+                    ));
+                }
+                Ok(_) => (),
+                Err(_) => {
+                    rendered.push_str(line);
+                    rendered.push('\n');
+                }
+            }
+        }
+        rendered
+    }
+
+    /// Picks the executable to package out of the executables cargo reported. A binary named
+    /// after the crate is preferred; otherwise, the crate must have exactly one binary target.
+    #[must_use]
+    pub(super) fn select_executable(
+        executables: &[(String, PathBuf)],
+        crate_name: &str,
+    ) -> Option<PathBuf> {
+        if let Some((_, path)) = executables.iter().find(|(name, _)| name == crate_name) {
+            return Some(path.clone());
+        }
+        match executables {
+            [(_, path)] => Some(path.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Sign all binaries created in the output dir specified in the [ConfigFile]. Will error if any
+/// errors occur during signing. If no `trusted_comment` is given, minisign's default trusted
+/// comment containing only a timestamp is used.
+pub(crate) fn sign_file(
+    config: &ConfigFile,
+    file: &[u8],
+    trusted_comment: Option<&str>,
+) -> Result<Vec<u8>, Error> {
+    crate::check_minisign()?;
+    let public_key = match PublicKey::from_base64(config.options.verifying_key.as_str()) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("The public/verifying key provided in the config file is not valid Base64: {e}");
+            return Err(Error::Config(
+                format!("Malformed verifying key: {e}").into(),
+            ));
+        }
+    };
+    match minisign::sign(
+        Some(&public_key),
         SECRET.get().expect("SECRET not set!"),
         file,
+        trusted_comment,
         None,
-        None,
-    )?.to_bytes())
+    ) {
+        Ok(signature) => Ok(signature.to_bytes()),
+        Err(e) => Err(Error::Signing(Box::new(e))),
+    }
 }
 
-/// Builds a crate source, signs it and verifies the signature.
-///
-/// `name` is the name of the folder of the crate source on disk.
-///
-/// Outputs a triple `(String, Vec<u8>, Vec<u8>)` on success, where
+/// The path of the log file the output of building `crate_name` for `target` is written to.
+#[must_use]
+pub(crate) fn build_log_path(config: &ConfigFile, crate_name: &str, target: &str) -> PathBuf {
+    log_dir(config).join(format!("{crate_name}-{target}.log"))
+}
+
+/// Locates the build log of the crate at `crate_path`, if the crate has been built during this
+/// run. Unlike [build_log_path], this only requires the path to the crate source, which must not
+/// have been deleted yet.
+#[must_use]
+pub(crate) fn find_build_log(config: &ConfigFile, crate_path: &Path) -> Option<PathBuf> {
+    let manifest = Manifest::from_path(crate_path.join("Cargo.toml")).ok()?;
+    let name = &manifest.package().name;
+    let rustc = rustc_version_verbose(config.toolchain(name)).ok()?;
+    let log_path = build_log_path(config, name, host_triple(&rustc).unwrap_or("unknown"));
+    log_path.exists().then_some(log_path)
+}
+
+/// Writes the invoked commands, their' stderr with the rendered compiler diagnostics and the
+/// artifacts cargo reported on stdout to the build log at `log_path`, replacing any previous log.
+/// Failing to write the log is not fatal and only logged as a warning.
+fn write_build_log(log_path: &Path, entries: &[(String, Output)]) {
+    let mut contents = Vec::new();
+    for (command, output) in entries {
+        contents.extend_from_slice(format!("$ {command}\n{}\n", output.status).as_bytes());
+        contents.extend_from_slice(b"\n--- stderr ---\n");
+        contents.extend_from_slice(&output.stderr);
+        contents.extend_from_slice(b"\n--- stdout ---\n");
+        contents.extend_from_slice(render_stdout(&output.stdout).as_bytes());
+        contents.push(b'\n');
+    }
+    match std::fs::write(log_path, contents) {
+        Ok(_) => debug!("Wrote build log to {log_path:?}"),
+        Err(e) => warn!("Could not write build log to {log_path:?}: {e}"),
+    }
+}
+
+/// Information about how a crate binary was built, written next to the signed archive as
+/// `<name>.build.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BuildMetadata {
+    /// Package name of the crate.
+    pub(crate) crate_name: String,
+    /// Package version of the crate.
+    pub(crate) crate_version: String,
+    /// File name of the executable inside of the archive, which is what cargo-binstall calls
+    /// `{ bin }`.
+    #[serde(default)]
+    pub(crate) binary: String,
+    /// The target triple the crate was built for.
+    #[serde(default)]
+    pub(crate) target: String,
+    /// ISO 8601 timestamp of when the build finished.
+    pub(crate) timestamp: String,
+    /// The pinned toolchain the crate was built with, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) toolchain: Option<String>,
+    /// Output of `rustc -vV` for the toolchain the crate was built with.
+    pub(crate) rustc: String,
+    /// Whether the build inherited the full environment of the host.
+    pub(crate) inherit_env: bool,
+    /// Names of the host environment variables which were passed through to the build.
+    pub(crate) passthrough_env: Vec<String>,
+    /// Environment variables set by warehouseify for the build.
+    pub(crate) env: BTreeMap<String, String>,
+    /// Whether the crate was built offline, inside of a sandbox.
+    pub(crate) sandboxed: bool,
+}
+
+/// A successfully built crate binary.
+#[derive(Debug)]
+pub(crate) struct BuiltCrate {
+    /// The name of the binary, including the file suffix. The name is formatted as
+    /// `[crate_name]-[crate-version]-[ISO 8601 build-timestamp]<.[suffix]>`.
+    pub(crate) name: String,
+    /// The entire binary, as bytes.
+    pub(crate) binary: Vec<u8>,
+    pub(crate) metadata: BuildMetadata,
+    /// Path to the full log of the build.
+    pub(crate) log: PathBuf,
+}
+
+/// Builds a crate source and returns the resulting [BuiltCrate].
 ///
-/// - `(String, ` is the name of the binary, including the file suffix. The name is formatted as
-///   `[crate_name]-[crate-version]-[ISO 8601 build-timestamp]<.[suffix]>`.
-/// - ` Vec<u8>)` contains the entire binary, as bytes.
+/// `crate_path` is the path of the folder of the crate source on disk.
 ///
 /// Will error, if
 ///
@@ -93,68 +370,118 @@ pub(crate) fn sign_file(config: &ConfigFile, file: &[u8]) -> Result<Vec<u8>, Std
 /// - The produced signature somehow doesn't match the computed signature
 /// - The crate fails to build
 /// - There is an I/O error
-pub(crate) fn build_crate(
-    config: &ConfigFile,
-    crate_path: &Path,
-) -> Result<(String, Vec<u8>), StdErrorS> {
+pub(crate) fn build_crate(config: &ConfigFile, crate_path: &Path) -> Result<BuiltCrate, Error> {
     let manifest_path = crate_path.join("Cargo.toml");
     trace!("Locating manifest at {manifest_path:?}");
-    let manifest = Manifest::from_path(manifest_path)?;
+    let manifest = Manifest::from_path(manifest_path).map_err(|e| Error::Build(Box::new(e)))?;
     let name = &manifest.package().name;
+    let toolchain = config.toolchain(name).map(String::from);
+    let rustc = rustc_version_verbose(toolchain.as_deref()).map_err(Error::Dependency)?;
+    // The sandbox only grants access to absolute paths
+    let crate_path = &std::path::absolute(crate_path)?;
+    let log_path = build_log_path(config, name, host_triple(&rustc).unwrap_or("unknown"));
+    let mut log_entries = Vec::new();
+    let limits = config.limits(name);
+    if config.options.sandbox {
+        info!("Vendoring dependencies of crate {name}...");
+        let vendor_command = create_vendor_command(config, crate_path, name);
+        let vendor_description = format!("{vendor_command:?}");
+        let (vendor_result, exceeded) = output_with_limits(vendor_command, &limits)?;
+        log_entries.push((vendor_description, vendor_result.clone()));
+        if let Some(exceeded) = exceeded {
+            write_build_log(&log_path, &log_entries);
+            error!("Vendoring dependencies of crate {name} failed: {exceeded}");
+            return Err(Error::BuildLimit(exceeded));
+        }
+        if !vendor_result.status.success() {
+            write_build_log(&log_path, &log_entries);
+            error!(
+                "cargo returned exit code {} when vendoring dependencies of crate {name}",
+                vendor_result.status
+            );
+            return Err(Error::Download(
+                format!(
+                    "cargo vendor returned exit code {}: {}",
+                    vendor_result.status,
+                    String::from_utf8_lossy(vendor_result.stderr.as_slice())
+                )
+                .into(),
+            ));
+        }
+        write_vendor_config(crate_path, &vendor_result.stdout)?;
+    }
     info!("Building crate {name}...");
-    let build_result = match create_build_command(config, crate_path, name).output() {
+    let build_command = create_build_command(config, crate_path, name);
+    let build_description = format!("{build_command:?}");
+    let (build_result, exceeded) = match output_with_limits(build_command, &limits) {
         Ok(out) => out,
         Err(e) => {
             error!("cargo process died unexpectedly: {e}");
-            panic!("Couldn't build binary");
+            return Err(Error::Build(
+                format!("cargo process died unexpectedly: {e}").into(),
+            ));
         }
     };
+    log_entries.push((build_description, build_result.clone()));
+    write_build_log(&log_path, &log_entries);
+    if let Some(exceeded) = exceeded {
+        error!("Building crate {name} failed: {exceeded}. See {log_path:?} for the build log.");
+        return Err(Error::BuildLimit(exceeded));
+    }
     if build_result.status.code() != Some(0) {
         error!(
-            "cargo returned exit code {} when building crate {name}",
+            "cargo returned exit code {} when building crate {name}. See {log_path:?} for the build log.",
             build_result.status
         );
-        return Err(format!(
-            "cargo returned exit code {}: {}",
-            build_result.status,
-            String::from_utf8_lossy(build_result.stderr.as_slice())
-        )
-        .into());
-    }
-    let release_binary_path = match crate_path
-        .join("target")
-        .join("release")
-        .join(name)
-        .canonicalize()
-    {
-        Ok(path) => {
+        return Err(Error::Build(
+            format!(
+                "cargo returned exit code {}: {}",
+                build_result.status,
+                String::from_utf8_lossy(build_result.stderr.as_slice())
+            )
+            .into(),
+        ));
+    }
+    let executables = find_executables(&build_result.stdout);
+    trace!("cargo reported the following executables: {executables:?}");
+    let release_binary_path = match select_executable(&executables, name) {
+        Some(path) => {
             trace!("Found release binary!");
             path
         }
-        Err(e) => {
+        None => {
             error!(
-                r#"Release binary "{name}" not found. Does the crate have a bin target under a different name? {e}"#
-            );
-            todo!(
-                "Cargo projects with multiple targets or target binaries with names different from the crate name not yet supported. This is a planned feature, though."
+                r#"Could not determine the release binary of crate "{name}". cargo reported the following binary targets: {:?}"#,
+                executables
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<&str>>()
             );
+            return Err(Error::Build(
+                String::from(
+                    "Cargo projects with multiple binary targets, none of which is named after the crate, are not yet supported. This is a planned feature, though.",
+                )
+                .into(),
+            ));
         }
     };
-    assert!(release_binary_path.exists());
-    assert!(release_binary_path.is_file());
     debug!("Trying to open release binary file at path {release_binary_path:?}");
 
-    let file_buf = match std::fs::read(release_binary_path) {
+    let file_buf = match std::fs::read(&release_binary_path) {
         Ok(contents) => contents,
         Err(e) => {
             error!("Reading the binary file failed: {e}");
-            panic!("I/O error");
+            return Err(Error::Io(e));
         }
     };
     let timestamp = iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string();
+    let binary = release_binary_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| name.to_owned());
 
     if config.options.autodelete_sources {
-        match std::fs::remove_dir_all(panic_on_dangerous_path(crate_path)) {
+        match std::fs::remove_dir_all(check_dangerous_path(crate_path)?) {
             Ok(_) => (),
             Err(e) => warn!(
                 "Unable to delete the sources for {name}; You will have to clean it up manually: {e}"
@@ -162,8 +489,239 @@ pub(crate) fn build_crate(
         };
     }
     info!("Done!");
-    Ok((
-        format!("{name}-{}-{timestamp}", manifest.package().version.get()?),
-        file_buf,
-    ))
+    let crate_version = manifest
+        .package()
+        .version
+        .get()
+        .map_err(|e| Error::Build(Box::new(e)))?
+        .to_owned();
+    Ok(BuiltCrate {
+        name: format!("{name}-{crate_version}-{timestamp}"),
+        binary: file_buf,
+        log: log_path,
+        metadata: BuildMetadata {
+            crate_name: name.to_owned(),
+            crate_version,
+            binary,
+            target: host_triple(&rustc).unwrap_or("unknown").to_owned(),
+            timestamp,
+            toolchain,
+            rustc,
+            inherit_env: config.options.inherit_env,
+            passthrough_env: match config.options.inherit_env {
+                true => Vec::new(),
+                false => PASSTHROUGH_ENV
+                    .iter()
+                    .filter(|var| std::env::var_os(var).is_some())
+                    .map(|var| var.to_string())
+                    .collect(),
+            },
+            env: build_env(config, name),
+            sandboxed: config.options.sandbox,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use super::build_command::{PASSTHROUGH_ENV, build_env, create_build_command};
+    use super::build_crate;
+    use super::cargo_messages::{find_executables, render_stdout, select_executable};
+    use super::{build_log_path, host_triple, log_dir, rustc_version_verbose};
+    use crate::ConfigFile;
+    use crate::error::Error;
+    use crate::process_crates::limits::LimitExceeded;
+    use crate::process_crates::sandbox::check_bubblewrap;
+    use crate::temp_dir::TempDir;
+
+    /// A config with no crates and the given `options`, which builds inside of `workspace`.
+    fn config(workspace: &Path, options: &str) -> ConfigFile {
+        toml::from_str(&format!(
+            "[crates]\n[options]\nworkspace_path = {workspace:?}\nverifying_key = \"\"\nautodelete_sources = false\n{options}\n[dependencies]\n"
+        ))
+        .unwrap()
+    }
+
+    /// Writes a crate named `tool` with the given build script to `dir`.
+    fn write_crate(dir: &Path, build_script: &str) {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"tool\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("build.rs"), build_script).unwrap();
+    }
+
+    #[test]
+    fn cleared_env_keeps_only_passthrough_and_configured_vars() {
+        let config = config(
+            Path::new("/warehouse"),
+            "target_dir = \"/warehouse/target\"\n[options.env]\nRUSTFLAGS = \"-C target-cpu=native\"",
+        );
+        let env = build_env(&config, "tool");
+        assert_eq!(env["CARGO_TARGET_DIR"], "/warehouse/target");
+        assert_eq!(env["RUSTFLAGS"], "-C target-cpu=native");
+
+        let command = create_build_command(&config, Path::new("/warehouse/build/tool"), "tool");
+        let envs = command
+            .get_envs()
+            .map(|(key, value)| (key.to_string_lossy().into_owned(), value))
+            .collect::<Vec<_>>();
+        // The host environment is cleared, so PATH must have been passed through explicitly
+        assert!(envs.iter().any(|(key, _)| key == "PATH"));
+        for (key, _) in &envs {
+            assert!(
+                PASSTHROUGH_ENV.contains(&key.as_str()) || env.contains_key(key),
+                "{key} was not cleared"
+            );
+        }
+        for (key, value) in &env {
+            assert!(
+                envs.iter()
+                    .any(|(set, set_value)| set == key && *set_value == Some(value.as_ref())),
+                "{key} was not set"
+            );
+        }
+    }
+
+    #[test]
+    fn inherited_env_only_adds_configured_vars() {
+        let config = config(
+            Path::new("/warehouse"),
+            "inherit_env = true\n[options.env]\nRUSTFLAGS = \"-C target-cpu=native\"",
+        );
+        let command = create_build_command(&config, Path::new("/warehouse/build/tool"), "tool");
+        let envs = command.get_envs().collect::<Vec<_>>();
+        assert_eq!(
+            envs,
+            [(
+                OsStr::new("RUSTFLAGS"),
+                Some(OsStr::new("-C target-cpu=native"))
+            )]
+        );
+    }
+
+    /// Builds a crate whose build script never finishes, but keeps writing to a heartbeat file,
+    /// and asserts that the build script no longer runs once the build has timed out.
+    fn assert_build_script_is_killed(sandbox: bool) {
+        let workspace = TempDir::new("build-timeout-test").unwrap();
+        let crate_path = workspace.join("tool");
+        write_crate(
+            &crate_path,
+            r#"fn main() {
+    let heartbeat = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("heartbeat");
+    for beat in 0u64.. {
+        std::fs::write(&heartbeat, beat.to_string()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+"#,
+        );
+        let config = config(
+            &workspace,
+            &format!("sandbox = {sandbox}\n[options.limits]\ntimeout = 10"),
+        );
+        match build_crate(&config, &crate_path) {
+            Err(Error::BuildLimit(LimitExceeded::Timeout(_))) => (),
+            result => panic!("build did not time out: {result:?}"),
+        }
+        let heartbeat = crate_path.join("heartbeat");
+        let beat = std::fs::read(&heartbeat).expect("build script did not run");
+        std::thread::sleep(Duration::from_secs(1));
+        assert_eq!(std::fs::read(&heartbeat).unwrap(), beat);
+    }
+
+    #[test]
+    fn kills_build_script_on_timeout() {
+        assert_build_script_is_killed(false);
+    }
+
+    #[test]
+    #[ignore = "requires bubblewrap"]
+    fn kills_sandboxed_build_script_on_timeout() {
+        check_bubblewrap().unwrap();
+        assert_build_script_is_killed(true);
+    }
+
+    const CARGO_OUTPUT: &str = r#"{"reason":"compiler-artifact","package_id":"registry+https://github.com/rust-lang/crates.io-index#clap@4.5.36","manifest_path":"/x/clap/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"clap","src_path":"/x/clap/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"profile":{},"features":[],"filenames":["/t/release/deps/libclap.rlib"],"executable":null,"fresh":true}
+{"reason":"compiler-artifact","package_id":"path+file:///x/tool#0.1.0","manifest_path":"/x/tool/Cargo.toml","target":{"kind":["custom-build"],"crate_types":["bin"],"name":"build-script-build","src_path":"/x/tool/build.rs","edition":"2021","doc":false,"doctest":false,"test":false},"profile":{},"features":[],"filenames":["/t/release/build/tool/build-script-build"],"executable":null,"fresh":false}
+{"reason":"compiler-artifact","package_id":"path+file:///x/tool#0.1.0","manifest_path":"/x/tool/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"tool-cli","src_path":"/x/tool/src/main.rs","edition":"2021","doc":true,"doctest":false,"test":true},"profile":{},"features":[],"filenames":["/t/release/tool-cli"],"executable":"/t/release/tool-cli","fresh":false}
+not json at all
+{"reason":"build-finished","success":true}"#;
+
+    #[test]
+    fn finds_only_bin_executables() {
+        let executables = find_executables(CARGO_OUTPUT.as_bytes());
+        assert_eq!(
+            executables,
+            vec![(
+                String::from("tool-cli"),
+                PathBuf::from("/t/release/tool-cli")
+            )]
+        );
+    }
+
+    #[test]
+    fn renders_only_artifacts() {
+        assert_eq!(
+            render_stdout(CARGO_OUTPUT.as_bytes()),
+            "artifact clap (lib): /t/release/deps/libclap.rlib
+artifact build-script-build (custom-build): /t/release/build/tool/build-script-build
+artifact tool-cli (bin): /t/release/tool-cli
+not json at all
+"
+        );
+    }
+
+    #[test]
+    fn failed_build_leaves_readable_log() {
+        let workspace = TempDir::new("build-log-test").unwrap();
+        let crate_path = workspace.join("tool");
+        write_crate(&crate_path, "fn main() {}\n");
+        std::fs::write(
+            crate_path.join("src/main.rs"),
+            "fn main() {\n    let _: u32 = \"text\";\n}\n",
+        )
+        .unwrap();
+        let config = config(&workspace, "");
+        std::fs::create_dir_all(log_dir(&config)).unwrap();
+        assert!(matches!(
+            build_crate(&config, &crate_path),
+            Err(Error::Build(_))
+        ));
+        let rustc = rustc_version_verbose(None).unwrap();
+        let log_path = build_log_path(&config, "tool", host_triple(&rustc).unwrap());
+        let log = std::fs::read_to_string(log_path).unwrap();
+        assert!(log.contains("error[E0308]: mismatched types"), "{log}");
+        assert!(log.contains("src/main.rs:2"), "{log}");
+        assert!(
+            log.contains("artifact build-script-build (custom-build)"),
+            "{log}"
+        );
+        assert!(!log.contains("\"reason\""), "{log}");
+    }
+
+    #[test]
+    fn selects_executable() {
+        let single = vec![(String::from("tool-cli"), PathBuf::from("/t/tool-cli"))];
+        assert_eq!(
+            select_executable(&single, "tool"),
+            Some(PathBuf::from("/t/tool-cli"))
+        );
+        let multiple = vec![
+            (String::from("tool-cli"), PathBuf::from("/t/tool-cli")),
+            (String::from("tool"), PathBuf::from("/t/tool")),
+        ];
+        assert_eq!(
+            select_executable(&multiple, "tool"),
+            Some(PathBuf::from("/t/tool"))
+        );
+        assert_eq!(select_executable(&multiple, "other"), None);
+    }
 }
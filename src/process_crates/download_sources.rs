@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use cargo_toml::Dependency;
 use log::{debug, error, info, trace, warn};
 
-use crate::StdErrorS;
+use crate::error::Error;
 use crate::process_crates::unpack_gzip_archive;
 
 use super::{CrateGitInformation, ExternalCrateSource, SortedCrates};
@@ -14,9 +14,7 @@ use super::{CrateGitInformation, ExternalCrateSource, SortedCrates};
 // this function is called for SECURITY REASONS! We should
 // also check AFTER downloading, ensuring that only the directories and files exist, which
 // we should have created.
-pub(crate) fn download_sources(
-    sources: SortedCrates,
-) -> Result<HashMap<String, Vec<u8>>, StdErrorS> {
+pub(crate) fn download_sources(sources: SortedCrates) -> Result<HashMap<String, Vec<u8>>, Error> {
     debug!("Starting download of external crate sources");
     let mut downloaded_sources = HashMap::new();
     let mut crates_io_sources = Vec::new();
@@ -53,7 +51,11 @@ pub(crate) fn download_sources(
         0 => info!("No crates.io sources to download."),
         num => {
             info!("Downloading {num} crates from crates.io...",);
-            match download_crates_io_sources(&crates_io_sources) {
+            match download_crates_io_sources(
+                &crates_io_sources,
+                CRATES_IO_STATIC_URL,
+                CRATES_IO_API_URL,
+            ) {
                 Ok(sources) => {
                     debug!(
                         "Successfully downloaded {} crates from crates.io!",
@@ -110,9 +112,18 @@ pub(crate) fn download_sources(
     Ok(downloaded_sources)
 }
 
+/// Base URL crate archives are downloaded from.
+const CRATES_IO_STATIC_URL: &str = "https://static.crates.io/crates";
+/// Base URL of the crates.io API, which crate archives are downloaded from if the static URL fails.
+const CRATES_IO_API_URL: &str = "https://crates.io/api/v1/crates";
+
+/// Downloads and decompresses the `.crate` archives of `sources` from `static_url`, falling back
+/// to `api_url`.
 fn download_crates_io_sources(
     sources: &[(String, Dependency)],
-) -> Result<HashMap<String, Vec<u8>>, StdErrorS> {
+    static_url: &str,
+    api_url: &str,
+) -> Result<HashMap<String, Vec<u8>>, Error> {
     debug!("Starting download of {} crates.io sources", sources.len());
     let mut downloaded: HashMap<String, Vec<u8>> = HashMap::new();
 
@@ -144,23 +155,21 @@ fn download_crates_io_sources(
                 }
                 None => {
                     error!("No version specified for crates.io dependency '{name}'");
-                    return Err(
+                    return Err(Error::Config(
                         format!("No version specified for crates.io dependency '{name}'").into(),
-                    );
+                    ));
                 }
             },
             Dependency::Inherited(_) => {
                 error!("Cannot deduce crate version for crate {name} from inherented dependency!");
-                return Err(
+                return Err(Error::Config(
                     String::from("Unable to parse crate version: Malformed configuration").into(),
-                );
+                ));
             }
         };
 
         // First try static.crates.io URL
-        let url = format!(
-            "https://static.crates.io/crates/{package_name}/{package_name}-{version}.crate"
-        );
+        let url = format!("{static_url}/{package_name}/{package_name}-{version}.crate");
         trace!("Attempting download from static URL: {url}");
 
         match minreq::get(&url).send() {
@@ -182,7 +191,7 @@ fn download_crates_io_sources(
         }
 
         // Fall back to API endpoint if static URL fails
-        let api_url = format!("https://crates.io/api/v1/crates/{package_name}/{version}/download");
+        let api_url = format!("{api_url}/{package_name}/{version}/download");
         trace!("Attempting download from API URL: {api_url}");
 
         match minreq::get(&api_url).send() {
@@ -198,16 +207,20 @@ fn download_crates_io_sources(
                         "Failed to download crate '{}': HTTP status {}",
                         name, api_response.status_code
                     );
-                    return Err(format!(
-                        "Failed to download crate '{}': HTTP status {}",
-                        name, api_response.status_code
-                    )
-                    .into());
+                    return Err(Error::Download(
+                        format!(
+                            "Failed to download crate '{}': HTTP status {}",
+                            name, api_response.status_code
+                        )
+                        .into(),
+                    ));
                 }
             }
             Err(e) => {
                 error!("API request failed for '{name}': {e}");
-                return Err(format!("API request failed for '{name}': {e}").into());
+                return Err(Error::Download(
+                    format!("API request failed for '{name}': {e}").into(),
+                ));
             }
         }
     }
@@ -223,7 +236,7 @@ fn download_crates_io_sources(
 // done later
 fn download_git_sources(
     sources: &[(String, ExternalCrateSource, Dependency)],
-) -> Result<HashMap<String, Vec<u8>>, StdErrorS> {
+) -> Result<HashMap<String, Vec<u8>>, Error> {
     debug!("Starting download of {} git sources", sources.len());
     let mut downloaded: HashMap<String, Vec<u8>> = HashMap::new();
 
@@ -242,12 +255,16 @@ fn download_git_sources(
                     }
                     None => {
                         error!("No git URL specified for dependency '{name}'");
-                        return Err(format!("No git URL specified for dependency '{name}'").into());
+                        return Err(Error::Config(
+                            format!("No git URL specified for dependency '{name}'").into(),
+                        ));
                     }
                 },
                 _ => {
                     error!("Invalid dependency format for git source '{name}'");
-                    return Err(format!("Invalid dependency format for git source '{name}'").into());
+                    return Err(Error::Config(
+                        format!("Invalid dependency format for git source '{name}'").into(),
+                    ));
                 }
             };
 
@@ -318,11 +335,13 @@ fn download_git_sources(
                                 "Failed to download git source '{}': HTTP status {}",
                                 name, response.status_code
                             );
-                            return Err(format!(
-                                "Failed to download git source '{}': HTTP status {}",
-                                name, response.status_code
-                            )
-                            .into());
+                            return Err(Error::Download(
+                                format!(
+                                    "Failed to download git source '{}': HTTP status {}",
+                                    name, response.status_code
+                                )
+                                .into(),
+                            ));
                         }
 
                         debug!(
@@ -335,7 +354,9 @@ fn download_git_sources(
                     }
                     Err(e) => {
                         error!("Request failed for git source '{name}': {e}");
-                        return Err(format!("Request failed for git source '{name}': {e}").into());
+                        return Err(Error::Download(
+                            format!("Request failed for git source '{name}': {e}").into(),
+                        ));
                     }
                 }
             } else if git_url.contains("gitlab.com") {
@@ -385,11 +406,13 @@ fn download_git_sources(
                                 "Failed to download git source '{}': HTTP status {}",
                                 name, response.status_code
                             );
-                            return Err(format!(
-                                "Failed to download git source '{}': HTTP status {}",
-                                name, response.status_code
-                            )
-                            .into());
+                            return Err(Error::Download(
+                                format!(
+                                    "Failed to download git source '{}': HTTP status {}",
+                                    name, response.status_code
+                                )
+                                .into(),
+                            ));
                         }
 
                         info!(
@@ -402,15 +425,17 @@ fn download_git_sources(
                     }
                     Err(e) => {
                         error!("Request failed for git source '{name}': {e}");
-                        return Err(format!("Request failed for git source '{name}': {e}").into());
+                        return Err(Error::Download(
+                            format!("Request failed for git source '{name}': {e}").into(),
+                        ));
                     }
                 }
             } else {
                 warn!("Git host not supported: {git_url}");
                 error!("Unsupported git host for dependency '{name}': {git_url}");
-                return Err(
+                return Err(Error::Config(
                     format!("Unsupported git host for dependency '{name}': {git_url}").into(),
-                );
+                ));
             }
         }
     }
@@ -418,3 +443,25 @@ fn download_git_sources(
     info!("Successfully downloaded {} git sources", downloaded.len());
     Ok(downloaded)
 }
+
+#[cfg(test)]
+mod test {
+    use cargo_toml::Dependency;
+
+    use super::download_crates_io_sources;
+    use crate::error::exit_code;
+    use crate::publish::mock_server::MockServer;
+
+    #[test]
+    fn corrupt_archives_are_extraction_errors() {
+        let server = MockServer::start(|_, _| (200, Vec::new(), b"not a gzip archive".to_vec()));
+        let sources = [(
+            String::from("tool"),
+            Dependency::Simple(String::from("1.0.0")),
+        )];
+        let error = download_crates_io_sources(&sources, &server.url, &server.url).unwrap_err();
+        assert_eq!(error.exit_code(), exit_code::EXTRACTION);
+        assert!(error.to_string().starts_with("extraction error: "));
+        assert_eq!(server.requests()[0].path, "/tool/tool-1.0.0.crate");
+    }
+}
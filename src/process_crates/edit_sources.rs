@@ -5,6 +5,36 @@ use log::{debug, error, trace};
 
 use crate::{ConfigFile, StdErrorS};
 
+/// The `[package.metadata.binstall]` section telling cargo-binstall to download archives from
+/// `pkg_url` and to verify them with the minisign `verifying_key`.
+#[must_use]
+pub(crate) fn binstall_metadata(pkg_url: &str, verifying_key: &str) -> toml::value::Table {
+    debug!(r#"Setting "pkg-url" = "{pkg_url}" in [package.metadata.binstall]"#);
+    let mut signing_table = toml::value::Table::new();
+    signing_table.insert(
+        "algorithm".to_string(),
+        toml::Value::String("minisign".to_string()),
+    );
+    signing_table.insert(
+        "pubkey".to_string(),
+        toml::Value::String(verifying_key.to_string()),
+    );
+    debug!("Pubkey set to '{verifying_key}'");
+    let mut binstall_table = toml::value::Table::new();
+    binstall_table.insert(
+        "pkg-url".to_string(),
+        toml::Value::String(pkg_url.to_owned()),
+    );
+    binstall_table.insert("pkg-fmt".to_string(), toml::Value::String("tar".to_owned()));
+    // the executable is packaged at the root of the archive
+    binstall_table.insert(
+        "bin-dir".to_string(),
+        toml::Value::String("{ bin }{ binary-ext }".to_owned()),
+    );
+    binstall_table.insert("signing".to_string(), toml::Value::Table(signing_table));
+    binstall_table
+}
+
 pub(crate) fn add_build_meta_info(
     full_crate_path: &Path,
     config: &ConfigFile,
@@ -78,97 +108,16 @@ pub(crate) fn add_build_meta_info(
         }
     };
 
-    if !metadata_table.contains_key("binstall") {
-        debug!("Creating [package.metadata.binstall] section");
-        metadata_table.insert(
-            "binstall".to_string(),
-            toml::Value::Table(toml::value::Table::new()),
-        );
+    if metadata_table.contains_key("binstall") {
+        debug!("Replacing pre-existing [package.metadata.binstall] section");
     } else {
-        debug!("Removing pre-existing [package.metadata.binstall] section");
-        metadata_table.remove("binstall");
         debug!("Creating [package.metadata.binstall] section");
-        metadata_table.insert(
-            "binstall".to_string(),
-            toml::Value::Table(toml::value::Table::new()),
-        );
-    }
-
-    // TODO: Now that we can assure that the "binstall" table did not exist before this point,
-    //       we can change the below code to fit this assumption better
-
-    let binstall_table = match metadata_table.get_mut("binstall") {
-        Some(value) => match value.as_table_mut() {
-            Some(table) => table,
-            None => {
-                error!("Binstall is not a table in Cargo.toml");
-                return Err(String::from("invalid binstall format").into());
-            }
-        },
-        None => {
-            error!("Failed to access binstall section");
-            return Err(String::from("failed to access binstall").into());
-        }
-    };
-
-    // debug!(
-    //     r#"Inserting "pkg-url" = "{}" into [package.metadata.binstall]"#,
-    //     &config.options.pkg_url
-    // );
-    // // insert the pkg_url from the config
-    // binstall_table.insert(
-    //     "pkg_url".to_string(),
-    //     toml::Value::String(config.options.pkg_url.to_owned()),
-    // );
-    debug!(r#"Inserting "pkg-fmt" = "tar" into [package.metadata.binstall]"#);
-    // insert the pkg_fmt (tar)
-    binstall_table.insert("pkg_fmt".to_string(), toml::Value::String("tar".to_owned()));
-
-    // Get or create the signing table
-    if !binstall_table.contains_key("signing") {
-        debug!("Creating [package.metadata.binstall.signing] section");
-        binstall_table.insert(
-            "signing".to_string(),
-            toml::Value::Table(toml::value::Table::new()),
-        );
     }
-
-    let signing_table = match binstall_table.get_mut("signing") {
-        Some(value) => match value.as_table_mut() {
-            Some(table) => table,
-            None => {
-                error!("Signing is not a table in Cargo.toml");
-                return Err(String::from("invalid signing format").into());
-            }
-        },
-        None => {
-            error!("Failed to access signing section");
-            return Err(String::from("failed to access signing").into());
-        }
-    };
-
-    // Check if the section already exists
-    let existing = signing_table.contains_key("algorithm") && signing_table.contains_key("pubkey");
-
-    // Set algorithm and pubkey
-    signing_table.insert(
-        "algorithm".to_string(),
-        toml::Value::String("minisign".to_string()),
-    );
-    signing_table.insert(
-        "pubkey".to_string(),
-        toml::Value::String(verifying_key.to_string()),
+    metadata_table.insert(
+        "binstall".to_string(),
+        toml::Value::Table(binstall_metadata(&config.pkg_url(), verifying_key)),
     );
 
-    if existing {
-        debug!("Updated existing [package.metadata.binstall.signing] section");
-    } else {
-        debug!("Created new [package.metadata.binstall.signing] section");
-    }
-
-    debug!("Algorithm set to 'minisign'");
-    debug!("Pubkey set to '{verifying_key}'");
-
     // Write the modified TOML back to the file
     let new_toml_content = match toml::to_string(&toml_value) {
         Ok(content) => content,
@@ -0,0 +1,249 @@
+use std::fmt::Display;
+use std::io::Read;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::{error, trace, warn};
+
+use crate::config::BuildLimits;
+
+/// How often a running build is checked for having exceeded its' timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long to wait for the output of a killed build to be collected.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+/// A build was aborted, because it exceeded one of the configured [BuildLimits].
+pub(crate) enum LimitExceeded {
+    /// The build ran for longer than the configured timeout and was killed.
+    Timeout(Duration),
+    /// A process of the build used more CPU time than allowed, in seconds.
+    CpuTime(u64),
+    /// A process of the build tried to allocate more memory than allowed, in MiB.
+    Memory(u64),
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::Timeout(timeout) => {
+                write!(f, "build timed out after {} seconds", timeout.as_secs())
+            }
+            LimitExceeded::CpuTime(seconds) => {
+                write!(f, "build exceeded the CPU time limit of {seconds} seconds")
+            }
+            LimitExceeded::Memory(mib) => {
+                write!(f, "build exceeded the memory limit of {mib} MiB")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Applies the resource limits of [BuildLimits] to `command`, which are inherited by every
+/// process the command spawns. If a timeout is set, the command is also moved into its' own
+/// process group, so that the entire group can be killed once the timeout is reached.
+fn apply_limits(command: &mut Command, limits: &BuildLimits) {
+    if limits.timeout.is_some() {
+        command.process_group(0);
+    }
+    let memory = limits
+        .memory
+        .map(|mib| mib.saturating_mul(1024 * 1024) as libc::rlim_t);
+    let cpu_time = limits.cpu_time.map(|seconds| seconds as libc::rlim_t);
+    if memory.is_none() && cpu_time.is_none() {
+        return;
+    }
+    // SAFETY: The closure runs between fork and exec and only calls setrlimit, which is
+    // async-signal-safe. It neither allocates nor touches any locks.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = memory {
+                let limit = libc::rlimit {
+                    rlim_cur: bytes,
+                    rlim_max: bytes,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(seconds) = cpu_time {
+                // The soft limit sends SIGXCPU, the hard limit is only a backstop
+                let limit = libc::rlimit {
+                    rlim_cur: seconds,
+                    rlim_max: seconds.saturating_add(5),
+                };
+                if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        })
+    };
+}
+
+/// Sends `SIGKILL` to every process in the process group of `child`. Sandboxed builds are not
+/// part of the group, since bwrap moves them into a new session. They are killed by bwrap's
+/// `--die-with-parent` instead, once bwrap itself, which leads the group, has been killed.
+fn kill_process_group(child: &Child) {
+    let Ok(pgid) = libc::pid_t::try_from(child.id()) else {
+        error!("Process id {} is out of range, cannot kill it", child.id());
+        return;
+    };
+    // SAFETY: kill has no memory safety preconditions. A negative pid addresses the group.
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } != 0 {
+        warn!(
+            "Failed to kill process group {pgid}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Reads `source` to its' end on a separate thread, so that a child process never blocks on a
+/// full pipe while we are waiting for it to exit.
+fn read_in_background(source: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut source) = source {
+            let _ = source.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Executes `command` with the given [BuildLimits] applied and collects its' [Output], like
+/// [Command::output] does. If the command runs for longer than the configured timeout, its'
+/// entire process group is killed. If the command failed because it exceeded one of the limits,
+/// the [LimitExceeded] is returned alongside whatever output was collected.
+pub(crate) fn output_with_limits(
+    mut command: Command,
+    limits: &BuildLimits,
+) -> std::io::Result<(Output, Option<LimitExceeded>)> {
+    apply_limits(&mut command, limits);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let timeout = limits.timeout.map(Duration::from_secs);
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(timeout) = timeout
+            && started.elapsed() >= timeout
+        {
+            error!(
+                "Process {} exceeded its' timeout of {} seconds, killing it",
+                child.id(),
+                timeout.as_secs()
+            );
+            kill_process_group(&child);
+            let status = child.wait()?;
+            let output = Output {
+                status,
+                stdout: join_with_grace_period(stdout),
+                stderr: join_with_grace_period(stderr),
+            };
+            return Ok((output, Some(LimitExceeded::Timeout(timeout))));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    trace!(
+        "Process exited with {status:?} after {:?}",
+        started.elapsed()
+    );
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    let exceeded = match output.status.success() {
+        true => None,
+        false => limit_exceeded(&output, limits),
+    };
+    Ok((output, exceeded))
+}
+
+/// Joins a reader thread spawned by [read_in_background], but gives up after
+/// [KILL_GRACE_PERIOD], in case a process which escaped the killed process group still holds the
+/// pipe open. In that case, the thread is left behind and nothing is returned.
+fn join_with_grace_period(reader: JoinHandle<Vec<u8>>) -> Vec<u8> {
+    let started = Instant::now();
+    while !reader.is_finished() {
+        if started.elapsed() >= KILL_GRACE_PERIOD {
+            warn!("Output of a killed process could not be collected");
+            return Vec::new();
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    reader.join().unwrap_or_default()
+}
+
+/// Tries to determine whether a failed build was aborted because it ran into one of the
+/// resource limits of [BuildLimits]. Since the limits apply to `rustc` and not to `cargo` itself,
+/// this is inferred from the signal and from the diagnostics cargo printed.
+#[must_use]
+fn limit_exceeded(output: &Output, limits: &BuildLimits) -> Option<LimitExceeded> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if let Some(seconds) = limits.cpu_time
+        && (output.status.signal() == Some(libc::SIGXCPU) || stderr.contains("SIGXCPU"))
+    {
+        return Some(LimitExceeded::CpuTime(seconds));
+    }
+    if let Some(mib) = limits.memory
+        && (stderr.contains("memory allocation of") || stderr.contains("out of memory"))
+    {
+        return Some(LimitExceeded::Memory(mib));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use super::{LimitExceeded, output_with_limits};
+    use crate::config::BuildLimits;
+
+    #[test]
+    fn kills_process_group_on_timeout() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo started; sleep 30 & sleep 30");
+        let limits = BuildLimits {
+            timeout: Some(1),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let (output, exceeded) = output_with_limits(command, &limits).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            exceeded,
+            Some(LimitExceeded::Timeout(Duration::from_secs(1)))
+        );
+        assert_eq!(output.stdout, b"started\n");
+    }
+
+    #[test]
+    fn collects_output_within_limits() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo out; echo err >&2");
+        let limits = BuildLimits {
+            timeout: Some(10),
+            memory: Some(1024),
+            cpu_time: Some(10),
+        };
+        let (output, exceeded) = output_with_limits(command, &limits).unwrap();
+        assert_eq!(exceeded, None);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+}
@@ -9,6 +9,8 @@ pub(crate) mod build_sources;
 #[cfg(feature = "http-client")]
 pub(crate) mod download_sources;
 pub(crate) mod edit_sources;
+pub(crate) mod limits;
+pub(crate) mod sandbox;
 #[cfg(feature = "http-client")]
 pub(crate) mod unpack_sources;
 
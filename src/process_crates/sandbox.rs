@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::{debug, error, trace};
+
+use crate::StdErrorS;
+
+/// Name of the directory inside of a crate source, which its' dependencies are vendored into.
+pub(crate) const VENDOR_DIR: &str = "vendor";
+/// Name of the cargo config file inside of a crate source, which points cargo to [VENDOR_DIR].
+pub(crate) const VENDOR_CONFIG: &str = "vendor.toml";
+
+/// System directories mounted read-only into the sandbox, which contain the linker, the C
+/// toolchain and shared libraries. Paths which don't exist on the host are skipped.
+const SYSTEM_PATHS: [&str; 11] = [
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/libx32",
+    "/etc/alternatives",
+    "/etc/ld.so.cache",
+    "/etc/ld.so.conf",
+    "/etc/ld.so.conf.d",
+];
+
+/// Returns an error, if `bwrap --version` cannot be executed successfully. Sandboxed builds require
+/// [bubblewrap](https://github.com/containers/bubblewrap) to be installed on the host.
+pub(crate) fn check_bubblewrap() -> Result<(), StdErrorS> {
+    match Command::new("bwrap").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            trace!(
+                "Found {}",
+                String::from_utf8_lossy(&output.stdout).trim_end()
+            );
+            Ok(())
+        }
+        Ok(output) => {
+            error!(
+                "Executing bwrap failed: Exit code {}. Is bubblewrap installed and available on your $PATH?",
+                output.status
+            );
+            Err(String::from("Could not execute bwrap successfully").into())
+        }
+        Err(e) => {
+            error!(
+                "Executing bwrap failed: {e}. Is bubblewrap installed and available on your $PATH?"
+            );
+            Err(String::from("Could not execute bwrap successfully").into())
+        }
+    }
+}
+
+/// Writes the source replacement config printed by `cargo vendor` to [VENDOR_CONFIG] inside of
+/// the crate source, so that it can be passed to `cargo build --config`.
+pub(crate) fn write_vendor_config(crate_path: &Path, vendor_stdout: &[u8]) -> std::io::Result<()> {
+    let config_path = crate_path.join(VENDOR_CONFIG);
+    std::fs::write(&config_path, vendor_stdout)?;
+    debug!("Wrote vendor config to {config_path:?}");
+    Ok(())
+}
+
+/// Returns the directory in the environment variable `variable`, like `$CARGO_HOME`, falling back
+/// to `default` inside of `$HOME`, like rustup and cargo do.
+#[must_use]
+fn toolchain_home(variable: &str, default: &str) -> Option<PathBuf> {
+    match std::env::var_os(variable) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(default)),
+    }
+}
+
+/// The parts of the Rust toolchain a sandboxed build needs: The rustup proxies in
+/// `$CARGO_HOME/bin`, and the installed toolchains and settings in `$RUSTUP_HOME`. The rest of
+/// `$CARGO_HOME` is not mounted, as it holds the registry token in `credentials.toml` and possibly
+/// other secrets, while sandboxed builds only use vendored sources anyway.
+#[must_use]
+fn toolchain_paths(cargo_home: Option<&Path>, rustup_home: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(cargo_home) = cargo_home {
+        paths.push(cargo_home.join("bin"));
+    }
+    if let Some(rustup_home) = rustup_home {
+        paths.push(rustup_home.join("settings.toml"));
+        paths.push(rustup_home.join("toolchains"));
+    }
+    paths
+}
+
+/// Mounts `path` read-only at the same location inside of the sandbox. Symlinks, like `/lib` on
+/// systems with a merged `/usr`, are recreated instead of being mounted.
+fn bind_read_only(sandbox: &mut Command, path: &Path) {
+    match std::fs::read_link(path) {
+        Ok(target) => sandbox.arg("--symlink").arg(target).arg(path),
+        Err(_) => sandbox.arg("--ro-bind-try").arg(path).arg(path),
+    };
+}
+
+/// Wraps `command` in a `bwrap` invocation, which runs it with all namespaces (including the
+/// network namespace) unshared. Only the system directories in [SYSTEM_PATHS] and the Rust
+/// toolchain, see [toolchain_paths], are mounted, read-only. Apart from a fresh `/tmp`, only the given `writable_paths`
+/// can be written to; everything else on the host, like `$HOME`, is not visible at all.
+/// The sandboxed processes are killed together with bwrap, so killing bwrap is enough to abort
+/// the build. Environment variables set on `command` are carried over. `clear_env` must be
+/// `true`, if the environment of `command` has been cleared.
+#[must_use]
+pub(crate) fn sandboxed(command: &Command, writable_paths: &[PathBuf], clear_env: bool) -> Command {
+    let toolchain = toolchain_paths(
+        toolchain_home("CARGO_HOME", ".cargo").as_deref(),
+        toolchain_home("RUSTUP_HOME", ".rustup").as_deref(),
+    );
+    sandboxed_with(command, &toolchain, writable_paths, clear_env)
+}
+
+/// Like [sandboxed], but mounts the given `toolchain` paths read-only, instead of the ones of
+/// the Rust toolchain on the host.
+#[must_use]
+fn sandboxed_with(
+    command: &Command,
+    toolchain: &[PathBuf],
+    writable_paths: &[PathBuf],
+    clear_env: bool,
+) -> Command {
+    let mut sandbox = Command::new("bwrap");
+    sandbox
+        .arg("--unshare-all")
+        .arg("--die-with-parent")
+        .arg("--new-session");
+    for path in SYSTEM_PATHS {
+        bind_read_only(&mut sandbox, Path::new(path));
+    }
+    sandbox
+        .args(["--dev", "/dev"])
+        .args(["--proc", "/proc"])
+        .args(["--tmpfs", "/tmp"]);
+    for path in toolchain {
+        trace!("Granting sandbox read access to {path:?}");
+        sandbox.arg("--ro-bind-try").arg(path).arg(path);
+    }
+    for path in writable_paths {
+        let path = std::path::absolute(path).unwrap_or(path.clone());
+        trace!("Granting sandbox write access to {path:?}");
+        sandbox.arg("--bind").arg(&path).arg(&path);
+    }
+    sandbox
+        .arg("--")
+        .arg(command.get_program())
+        .args(command.get_args());
+    if clear_env {
+        sandbox.env_clear();
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => sandbox.env(key, value),
+            None => sandbox.env_remove(key),
+        };
+    }
+    sandbox.env("TMPDIR", "/tmp");
+    sandbox
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use super::{check_bubblewrap, sandboxed, sandboxed_with, toolchain_paths};
+    use crate::temp_dir::TempDir;
+
+    /// Creates a `$CARGO_HOME` and a `$RUSTUP_HOME` inside of `dir`, with a registry token in
+    /// `credentials.toml`, and returns the toolchain paths a sandbox would mount of them.
+    fn fake_toolchain(dir: &Path) -> Vec<PathBuf> {
+        let (cargo_home, rustup_home) = (dir.join("cargo"), dir.join("rustup"));
+        std::fs::create_dir_all(cargo_home.join("bin")).unwrap();
+        std::fs::create_dir_all(rustup_home.join("toolchains")).unwrap();
+        std::fs::write(cargo_home.join("bin/cargo"), "proxy").unwrap();
+        std::fs::write(rustup_home.join("settings.toml"), "").unwrap();
+        std::fs::write(
+            cargo_home.join("credentials.toml"),
+            "[registry]\ntoken = \"secret\"\n",
+        )
+        .unwrap();
+        toolchain_paths(Some(&cargo_home), Some(&rustup_home))
+    }
+
+    #[test]
+    fn wraps_command_in_bwrap() {
+        let mut command = Command::new("cargo");
+        command
+            .arg("build")
+            .env_clear()
+            .env("RUSTFLAGS", "-C target-cpu=native");
+        let sandbox = sandboxed(&command, &[PathBuf::from("/home/runner/build/tool")], true);
+        assert_eq!(sandbox.get_program(), "bwrap");
+        let args = sandbox.get_args().collect::<Vec<&OsStr>>();
+        assert!(args.contains(&OsStr::new("--unshare-all")));
+        assert!(
+            !args
+                .windows(3)
+                .any(|window| window == ["--ro-bind", "/", "/"])
+        );
+        assert!(
+            args.windows(3)
+                .any(|window| window == ["--ro-bind-try", "/usr", "/usr"])
+        );
+        assert!(args.windows(3).any(|window| window
+            == [
+                "--bind",
+                "/home/runner/build/tool",
+                "/home/runner/build/tool"
+            ]));
+        assert_eq!(args[args.len() - 3..], ["--", "cargo", "build"]);
+        assert!(
+            sandbox.get_envs().any(|(key, value)| key == "RUSTFLAGS"
+                && value == Some(OsStr::new("-C target-cpu=native")))
+        );
+    }
+
+    #[test]
+    #[ignore = "requires bubblewrap"]
+    fn runs_toolchain_but_hides_home() {
+        check_bubblewrap().unwrap();
+        let mut command = Command::new("cargo");
+        command.arg("--version");
+        let output = sandboxed(&command, &[], false).output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+        let secret = home.join(format!(".warehouseify-sandbox-{}", std::process::id()));
+        std::fs::write(&secret, "secret").unwrap();
+        let mut command = Command::new("cat");
+        command.arg(&secret);
+        let output = sandboxed(&command, &[], false).output();
+        std::fs::remove_file(&secret).unwrap();
+        let output = output.unwrap();
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn mounts_only_binaries_of_cargo_home() {
+        let dir = TempDir::new("sandbox-toolchain-test").unwrap();
+        let toolchain = fake_toolchain(&dir);
+        let credentials = dir.join("cargo/credentials.toml");
+        assert!(toolchain.contains(&dir.join("cargo/bin")));
+        assert!(toolchain.iter().all(|path| !credentials.starts_with(path)));
+        let sandbox = sandboxed_with(&Command::new("cargo"), &toolchain, &[], false);
+        assert!(
+            sandbox
+                .get_args()
+                .map(Path::new)
+                .all(|arg| { !(arg.starts_with(&*dir) && credentials.starts_with(arg)) })
+        );
+    }
+
+    #[test]
+    #[ignore = "requires bubblewrap"]
+    fn hides_cargo_credentials() {
+        check_bubblewrap().unwrap();
+        let dir = TempDir::new("sandbox-credentials-test").unwrap();
+        let toolchain = fake_toolchain(&dir);
+        let run = |path: PathBuf| {
+            let mut command = Command::new("cat");
+            command.arg(path);
+            sandboxed_with(&command, &toolchain, &[], false)
+                .output()
+                .unwrap()
+        };
+        assert!(run(dir.join("cargo/bin/cargo")).status.success());
+        let output = run(dir.join("cargo/credentials.toml"));
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+}
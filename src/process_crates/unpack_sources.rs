@@ -5,12 +5,15 @@ use std::path::{Path, PathBuf};
 use flate2::bufread::GzDecoder;
 use log::error;
 
-use crate::{ConfigFile, StdErrorS};
+use crate::ConfigFile;
+use crate::error::Error;
 
-pub(crate) fn unpack_gzip_archive(gzip_archive: Vec<u8>) -> Result<Vec<u8>, StdErrorS> {
+pub(crate) fn unpack_gzip_archive(gzip_archive: Vec<u8>) -> Result<Vec<u8>, Error> {
     let mut gzip_decoder = GzDecoder::new(gzip_archive.as_slice());
     let mut data_buffer = Vec::with_capacity(gzip_archive.len());
-    gzip_decoder.read_to_end(&mut data_buffer)?;
+    gzip_decoder
+        .read_to_end(&mut data_buffer)
+        .map_err(|e| Error::Extraction(Box::new(e)))?;
     Ok(data_buffer)
 }
 
@@ -24,7 +27,7 @@ pub(crate) fn unpack_gzip_archive(gzip_archive: Vec<u8>) -> Result<Vec<u8>, StdE
 pub(crate) fn write_tar_to_build_dir(
     tarball: Vec<u8>,
     path_to_package: &Path,
-) -> Result<(), StdErrorS> {
+) -> Result<(), Error> {
     let destination_path = match path_to_package.ends_with("build") {
         true => path_to_package,
         false => {
@@ -35,7 +38,9 @@ pub(crate) fn write_tar_to_build_dir(
         }
     };
     let mut tarball_reader = tar::Archive::new(tarball.as_slice());
-    tarball_reader.unpack(destination_path)?;
+    tarball_reader
+        .unpack(destination_path)
+        .map_err(|e| Error::Extraction(Box::new(e)))?;
     Ok(())
 }
 
@@ -51,10 +56,15 @@ pub(crate) fn artifact_dir(config: &ConfigFile) -> PathBuf {
     config.options.workspace_path.join("artifacts/")
 }
 
-/// Panics, if the path is unsafe (e.g. `/`, `/etc`, `/var`, `/etc/.../` ...)
-#[allow(clippy::expect_used)]
-#[must_use]
-pub(crate) fn panic_on_dangerous_path(path: &Path) -> PathBuf {
+/// Get the path to the directory where the build logs of all crates are written to.
+/// Does NOT panic if the path is unsafe (e.g. `/`, `/etc`, `/var`, ...)
+pub(crate) fn log_dir(config: &ConfigFile) -> PathBuf {
+    config.options.workspace_path.join("logs/")
+}
+
+/// Errors, if the path is unsafe (e.g. `/`, `/etc`, `/var`, `/etc/.../` ...). Returns the path,
+/// canonicalized if it was relative, otherwise.
+pub(crate) fn check_dangerous_path(path: &Path) -> Result<PathBuf, Error> {
     let path = if path.is_relative() {
         &match path.canonicalize() {
             Ok(path) => path,
@@ -63,19 +73,22 @@ pub(crate) fn panic_on_dangerous_path(path: &Path) -> PathBuf {
                     r#"Error when attempting to canonicalize path {}: {e}"#,
                     path.to_string_lossy()
                 );
-                panic!("Error canonicalizing path");
+                return Err(Error::Io(e));
             }
         }
     } else {
         path
     };
     if path.is_absolute() && path.components().collect::<Vec<_>>().len() < 4 {
-        panic!(
+        error!(
             "Given path {} looks too dangerous. Aborting.",
             path.to_string_lossy()
-        )
+        );
+        Err(Error::Config(
+            format!("Given path {} looks too dangerous", path.to_string_lossy()).into(),
+        ))
     } else {
-        path.to_owned()
+        Ok(path.to_owned())
     }
 }
 
@@ -83,46 +96,49 @@ pub(crate) fn panic_on_dangerous_path(path: &Path) -> PathBuf {
 mod test {
     use std::path::Path;
 
-    use super::panic_on_dangerous_path;
+    use super::check_dangerous_path;
+    use crate::error::Error;
+
+    /// Asserts that `path` is refused by the guard, not because of some unrelated error.
+    fn assert_too_dangerous(path: &str) {
+        match check_dangerous_path(Path::new(path)) {
+            Err(Error::Config(e)) => assert!(e.to_string().contains("looks too dangerous")),
+            result => panic!("{path} was not refused as too dangerous: {result:?}"),
+        }
+    }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_root_dir() {
-        panic_on_dangerous_path(Path::new("/"));
+    fn err_on_root_dir() {
+        assert_too_dangerous("/");
     }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_etc_dir() {
-        panic_on_dangerous_path(Path::new("/etc/"));
+    fn err_on_etc_dir() {
+        assert_too_dangerous("/etc/");
     }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_etc_subdir() {
-        panic_on_dangerous_path(Path::new("/etc/subdir/"));
+    fn err_on_etc_subdir() {
+        assert_too_dangerous("/etc/subdir/");
     }
 
     #[test]
     fn ok_on_some_homedir() {
-        panic_on_dangerous_path(Path::new("/home/runner/repo/"));
+        assert!(check_dangerous_path(Path::new("/home/runner/repo/")).is_ok());
     }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_relative_root_dir() {
-        panic_on_dangerous_path(Path::new("../../../../../../"));
+    fn err_on_relative_root_dir() {
+        assert_too_dangerous("../../../../../../");
     }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_relative_etc_dir() {
-        panic_on_dangerous_path(Path::new("../../../../../../etc"));
+    fn err_on_relative_etc_dir() {
+        assert_too_dangerous("../../../../../../etc");
     }
 
     #[test]
-    #[should_panic = "looks too dangerous"]
-    fn panic_on_relative_etc_subdir() {
-        panic_on_dangerous_path(Path::new("../../../../../../etc/systemd/"));
+    fn err_on_relative_etc_subdir() {
+        assert_too_dangerous("../../../../../../etc/systemd/");
     }
 }
@@ -0,0 +1,396 @@
+use log::{debug, info};
+use minreq::{Method, Request, Response};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::publish::index::index_files;
+use crate::publish::s3::uri_encode;
+use crate::publish::{
+    Artifact, GithubConfig, group_releases, is_up_to_date, release_files, release_tag,
+};
+
+/// Version of the GitHub REST API the requests are written against.
+const API_VERSION: &str = "2022-11-28";
+/// Tag of the release holding the index of the warehouse.
+pub(crate) const INDEX_RELEASE_TAG: &str = "warehouse-index";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    id: u64,
+    /// URI template for uploading assets, e.g.
+    /// `https://uploads.github.com/repos/o/r/releases/1/assets{?name,label}`.
+    upload_url: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    id: u64,
+    name: String,
+    /// Digest of the asset, e.g. `sha256:<hex>`. Not set for assets uploaded before GitHub
+    /// started computing digests.
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct NewRelease<'a> {
+    tag_name: &'a str,
+    name: &'a str,
+    body: &'a str,
+}
+
+/// A minimal client for the parts of the GitHub REST API needed to publish releases.
+pub(crate) struct Client<'a> {
+    config: &'a GithubConfig,
+    token: Zeroizing<String>,
+}
+
+impl<'a> Client<'a> {
+    /// Creates a client authenticating with the token in the environment variable configured in
+    /// `token_env`.
+    pub(crate) fn from_env(config: &'a GithubConfig) -> Result<Self, Error> {
+        match std::env::var(&config.token_env) {
+            Ok(token) if !token.is_empty() => Ok(Self {
+                config,
+                token: Zeroizing::new(token),
+            }),
+            _ => Err(Error::Config(
+                format!(
+                    "set ${} to a GitHub token which may create releases in {}",
+                    config.token_env, config.repository
+                )
+                .into(),
+            )),
+        }
+    }
+
+    fn request(&self, method: Method, url: &str) -> Request {
+        Request::new(method, url)
+            .with_header("Accept", "application/vnd.github+json")
+            .with_header("Authorization", format!("Bearer {}", self.token.as_str()))
+            .with_header("X-GitHub-Api-Version", API_VERSION)
+            .with_header(
+                "User-Agent",
+                concat!("warehouseify/", env!("CARGO_PKG_VERSION")),
+            )
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!(
+            "{}/repos/{}/{path}",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.repository
+        )
+    }
+
+    /// Sends `request` and returns the response, if its' status code is one of `expected`.
+    fn send(request: Request, expected: &[i32]) -> Result<Response, Error> {
+        let response = request.send().map_err(|e| Error::Publish(Box::new(e)))?;
+        match expected.contains(&response.status_code) {
+            true => Ok(response),
+            false => Err(Error::Publish(
+                format!(
+                    "GitHub API returned {} {}: {}",
+                    response.status_code,
+                    response.reason_phrase,
+                    response.as_str().unwrap_or_default()
+                )
+                .into(),
+            )),
+        }
+    }
+
+    fn parse<T: for<'de> Deserialize<'de>>(response: &Response) -> Result<T, Error> {
+        serde_json::from_slice(response.as_bytes()).map_err(|e| Error::Publish(Box::new(e)))
+    }
+
+    /// Returns the release tagged `tag`, creating it if it does not exist yet.
+    fn get_or_create_release(&self, tag: &str, body: &str) -> Result<Release, Error> {
+        let response = Self::send(
+            self.request(Method::Get, &self.api_url(&format!("releases/tags/{tag}"))),
+            &[200, 404],
+        )?;
+        if response.status_code == 200 {
+            debug!("Release {tag} exists");
+            return Self::parse(&response);
+        }
+        info!("Creating release {tag}");
+        let new_release = NewRelease {
+            tag_name: tag,
+            name: tag,
+            body,
+        };
+        let response = Self::send(
+            self.request(Method::Post, &self.api_url("releases"))
+                .with_header("Content-Type", "application/json")
+                .with_body(
+                    serde_json::to_vec(&new_release).map_err(|e| Error::Publish(Box::new(e)))?,
+                ),
+            &[201],
+        )?;
+        Self::parse(&response)
+    }
+
+    /// Makes sure that the release tagged `tag` exists and contains exactly the given `files` as
+    /// assets. Assets whose digest already matches are not uploaded again; assets with the same
+    /// name but different contents are replaced. Returns the number of uploaded files.
+    pub(crate) fn publish_release(
+        &self,
+        tag: &str,
+        body: &str,
+        files: &[(String, Vec<u8>)],
+    ) -> Result<usize, Error> {
+        let release = self.get_or_create_release(tag, body)?;
+        let upload_url = release
+            .upload_url
+            .split_once('{')
+            .map_or(release.upload_url.as_str(), |(url, _)| url);
+        let mut uploaded = 0;
+        let mut unchanged = Vec::new();
+        for (name, contents) in files {
+            if let Some(asset) = release.assets.iter().find(|asset| &asset.name == name) {
+                let published = asset
+                    .digest
+                    .as_deref()
+                    .map(|digest| digest.strip_prefix("sha256:").unwrap_or_default());
+                if is_up_to_date(&mut unchanged, name, contents, published) {
+                    debug!("{name} is already part of release {tag}");
+                    continue;
+                }
+                debug!("Replacing {name} in release {tag}");
+                Self::send(
+                    self.request(
+                        Method::Delete,
+                        &self.api_url(&format!("releases/assets/{}", asset.id)),
+                    ),
+                    &[204],
+                )?;
+            }
+            debug!("Uploading {name} to release {tag}");
+            Self::send(
+                self.request(
+                    Method::Post,
+                    &format!("{upload_url}?name={}", uri_encode(name, true)),
+                )
+                .with_header("Content-Type", "application/octet-stream")
+                .with_body(contents.as_slice()),
+                &[201],
+            )?;
+            uploaded += 1;
+        }
+        info!(
+            "Release {tag} (id {}) is up to date, uploaded {uploaded} of {} files",
+            release.id,
+            files.len()
+        );
+        Ok(uploaded)
+    }
+}
+
+/// Publishes one GitHub release per crate version, tagged `<crate>-v<version>`, containing the
+/// archives of all targets, their signatures and signed checksum files. The signed index of the
+/// warehouse is published as the release tagged [INDEX_RELEASE_TAG].
+pub(crate) fn publish(
+    config: &ConfigFile,
+    github: &GithubConfig,
+    artifacts: &[Artifact],
+) -> Result<(), Error> {
+    let client = Client::from_env(github)?;
+    for ((crate_name, version), release_artifacts) in group_releases(artifacts) {
+        let files = release_files(config, &release_artifacts)?;
+        let body = format!(
+            "Signed binaries of {crate_name} {version}, published by warehouseify. Install them with `cargo binstall {crate_name}@{version}`."
+        );
+        client.publish_release(&release_tag(crate_name, version), &body, &files)?;
+    }
+    client.publish_release(
+        INDEX_RELEASE_TAG,
+        "Signed index of every crate in this warehouse.",
+        &index_files(config)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::Client;
+    use crate::checksums::sha256_hex;
+    use crate::publish::GithubConfig;
+    use crate::publish::mock_server::{MockRequest, MockResponse, MockServer};
+
+    /// Asset id, name and digest.
+    type MockAsset = (u64, String, String);
+
+    /// Decodes a query parameter value like GitHub does, where `+` stands for a space.
+    fn query_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut index = 0;
+        while index < bytes.len() {
+            match bytes[index] {
+                b'%' => {
+                    let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap();
+                    decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                    index += 2;
+                }
+                b'+' => decoded.push(b' '),
+                byte => decoded.push(byte),
+            }
+            index += 1;
+        }
+        String::from_utf8(decoded).unwrap()
+    }
+
+    /// Answers like the GitHub API would, for a single repository `o/r`.
+    fn github_api() -> impl FnMut(&MockRequest, &str) -> MockResponse {
+        let mut releases: HashMap<String, (u64, Vec<MockAsset>)> = HashMap::new();
+        let mut next_id = 1;
+        move |request, url| {
+            let release_json = |id: u64, assets: &[MockAsset]| {
+                json!({
+                    "id": id,
+                    "upload_url": format!("{url}/uploads/repos/o/r/releases/{id}/assets{{?name,label}}"),
+                    "assets": assets.iter().map(|(id, name, digest)| json!({"id": id, "name": name, "digest": digest})).collect::<Vec<_>>(),
+                })
+                .to_string()
+                .into_bytes()
+            };
+            let path = request.path.as_str();
+            match request.method.as_str() {
+                "GET" => match path.strip_prefix("/repos/o/r/releases/tags/") {
+                    Some(tag) => match releases.get(tag) {
+                        Some((id, assets)) => (200, vec![], release_json(*id, assets)),
+                        None => (404, vec![], b"{}".to_vec()),
+                    },
+                    None => (404, vec![], b"{}".to_vec()),
+                },
+                "POST" if path == "/repos/o/r/releases" => {
+                    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                    let tag = body["tag_name"].as_str().unwrap().to_owned();
+                    next_id += 1;
+                    releases.insert(tag, (next_id, Vec::new()));
+                    (201, vec![], release_json(next_id, &[]))
+                }
+                "POST" => {
+                    let (release, name) = path
+                        .strip_prefix("/uploads/repos/o/r/releases/")
+                        .and_then(|rest| rest.split_once("/assets?name="))
+                        .unwrap();
+                    let release = release.parse::<u64>().unwrap();
+                    next_id += 1;
+                    let digest = format!("sha256:{}", sha256_hex(&request.body));
+                    for (id, assets) in releases.values_mut() {
+                        if *id == release {
+                            assets.push((next_id, query_decode(name), digest.clone()));
+                        }
+                    }
+                    (201, vec![], b"{}".to_vec())
+                }
+                "DELETE" => {
+                    let asset = path
+                        .strip_prefix("/repos/o/r/releases/assets/")
+                        .and_then(|id| id.parse::<u64>().ok())
+                        .unwrap();
+                    for (_, assets) in releases.values_mut() {
+                        assets.retain(|(id, _, _)| *id != asset);
+                    }
+                    (204, vec![], Vec::new())
+                }
+                _ => (405, vec![], Vec::new()),
+            }
+        }
+    }
+
+    #[test]
+    fn publishes_releases_idempotently() {
+        let server = MockServer::start(github_api());
+        let config: GithubConfig = toml::from_str(&format!(
+            "repository = \"o/r\"\napi_url = \"{}\"",
+            server.url
+        ))
+        .unwrap();
+        let client = Client {
+            config: &config,
+            token: String::from("token").into(),
+        };
+        let mut files = vec![
+            (
+                String::from("tool-x86_64-unknown-linux-gnu-v1.0.0.tar"),
+                b"archive".to_vec(),
+            ),
+            (
+                String::from("tool-x86_64-unknown-linux-gnu-v1.0.0.tar.sig"),
+                b"signature".to_vec(),
+            ),
+        ];
+        assert_eq!(
+            client.publish_release("tool-v1.0.0", "", &files).unwrap(),
+            2
+        );
+        assert_eq!(
+            client.publish_release("tool-v1.0.0", "", &files).unwrap(),
+            0
+        );
+        files[1].1 = b"new signature".to_vec();
+        assert_eq!(
+            client.publish_release("tool-v1.0.0", "", &files).unwrap(),
+            1
+        );
+
+        let requests = server.requests();
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.header("authorization") == Some("Bearer token"))
+        );
+        let count = |method: &str| {
+            requests
+                .iter()
+                .filter(|request| request.method == method)
+                .count()
+        };
+        // One release created, three uploads, one replaced asset deleted
+        assert_eq!(count("POST"), 4);
+        assert_eq!(count("DELETE"), 1);
+        assert_eq!(count("GET"), 3);
+    }
+
+    #[test]
+    fn encodes_asset_names() {
+        let server = MockServer::start(github_api());
+        let config: GithubConfig = toml::from_str(&format!(
+            "repository = \"o/r\"\napi_url = \"{}\"",
+            server.url
+        ))
+        .unwrap();
+        let client = Client {
+            config: &config,
+            token: String::from("token").into(),
+        };
+        // Build metadata in the version must not turn into a space, or the asset never matches
+        let files = vec![(
+            String::from("tool-x86_64-unknown-linux-gnu-v1.0.0+build.tar"),
+            b"archive".to_vec(),
+        )];
+        assert_eq!(
+            client
+                .publish_release("tool-v1.0.0+build", "", &files)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            client
+                .publish_release("tool-v1.0.0+build", "", &files)
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use crate::publish::index::{Index, IndexEntry};
+
+/// File name of the browsable HTML page listing the contents of the warehouse.
+pub(crate) const HTML_FILE: &str = "index.html";
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:72rem;margin:2rem auto;padding:0 1rem;color:#222}\
+table{border-collapse:collapse;width:100%;margin-bottom:1rem}\
+th,td{text-align:left;padding:.3rem .6rem;border-bottom:1px solid #ddd;vertical-align:top}\
+code,pre{font-family:ui-monospace,monospace;font-size:.85rem}\
+pre{background:#f4f4f4;padding:.6rem;overflow-x:auto}\
+.digest{word-break:break-all}";
+
+/// Escapes `text` for use in HTML text and attribute values.
+#[must_use]
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Orders versions newest first. Versions which are not valid semver are ordered after all others.
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (semver::Version::parse(a), semver::Version::parse(b)) {
+        (Ok(a), Ok(b)) => b.cmp(&a),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => b.cmp(a),
+    }
+}
+
+/// Renders a static HTML page from `index`, listing every crate with its' install command, and
+/// each version and target with links to the archive and its' signature and the archive checksum.
+/// The page does not load any external resources, so it can be served from anywhere, e.g. GitHub
+/// Pages.
+#[must_use]
+pub(crate) fn render_html(index: &Index) -> String {
+    let mut crates = BTreeMap::<&str, Vec<&IndexEntry>>::new();
+    for entry in index.archives.iter() {
+        crates.entry(&entry.crate_name).or_default().push(entry);
+    }
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>Warehouse</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>Warehouse</h1>\n<p>{} crates, {} archives. Archives are signed with minisign and can be installed with <a href=\"https://github.com/cargo-bins/cargo-binstall\">cargo-binstall</a>.</p>\n<h2>Public key</h2>\n<pre>{}</pre>\n<p>To verify an archive by hand, run <code>minisign -Vm &lt;archive&gt; -P {}</code>.</p>\n",
+        crates.len(),
+        index.archives.len(),
+        escape(&index.verifying_key),
+        escape(&index.verifying_key)
+    );
+    for (crate_name, mut entries) in crates {
+        entries.sort_by(|a, b| {
+            compare_versions(&a.version, &b.version).then_with(|| a.target.cmp(&b.target))
+        });
+        let crate_name = escape(crate_name);
+        html.push_str(&format!(
+            "<h2 id=\"{crate_name}\">{crate_name}</h2>\n<pre>cargo binstall {crate_name}</pre>\n<table>\n<tr><th>Version</th><th>Target</th><th>Archive</th><th>SHA-256</th></tr>\n"
+        ));
+        for entry in entries {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td><a href=\"{}\">archive</a> (<a href=\"{}\">signature</a>)</td><td class=\"digest\"><code>{}</code></td></tr>\n",
+                escape(&entry.version),
+                escape(&entry.target),
+                escape(&entry.url),
+                escape(&entry.signature_url),
+                escape(&entry.sha256)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::render_html;
+    use crate::publish::index::{Index, IndexEntry};
+
+    #[test]
+    fn renders_newest_versions_first() {
+        let entry = |version: &str| IndexEntry {
+            crate_name: String::from("tool"),
+            version: version.to_owned(),
+            target: String::from("x86_64-unknown-linux-gnu"),
+            url: format!("https://example.com/tool/{version}/tool.tar?a=1&b=2"),
+            sha256: String::from("abc"),
+            signature_url: String::from("https://example.com/tool.tar.sig"),
+            signature: String::new(),
+        };
+        let index = Index {
+            verifying_key: String::from("RWQkey"),
+            archives: vec![entry("1.9.0"), entry("1.10.0")],
+        };
+        let html = render_html(&index);
+        assert!(html.contains("<pre>cargo binstall tool</pre>"));
+        assert!(html.contains("<pre>RWQkey</pre>"));
+        assert!(html.contains("tool.tar?a=1&amp;b=2"));
+        assert!(html.find("1.10.0").unwrap() < html.find("1.9.0").unwrap());
+    }
+}
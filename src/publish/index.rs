@@ -0,0 +1,123 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::checksums::sha256_hex;
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::{artifact_dir, sign_file};
+use crate::publish::html::{HTML_FILE, render_html};
+use crate::publish::{Artifact, collect_artifacts, write_atomically, write_if_changed};
+use crate::signatures::{signature_path, verify_file, verifying_keys};
+
+/// File name of the index of the warehouse, written to the artifacts directory alongside its'
+/// signature.
+pub(crate) const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Machine readable list of every archive a warehouse contains, written as [INDEX_FILE].
+pub(crate) struct Index {
+    /// Minisign public key the archives can be verified with.
+    pub(crate) verifying_key: String,
+    /// Sorted by crate name, version and target.
+    pub(crate) archives: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexEntry {
+    #[serde(rename = "crate")]
+    pub(crate) crate_name: String,
+    pub(crate) version: String,
+    pub(crate) target: String,
+    /// Where the archive can be downloaded from.
+    pub(crate) url: String,
+    /// SHA-256 digest of the archive.
+    pub(crate) sha256: String,
+    /// Where the minisign signature of the archive can be downloaded from.
+    pub(crate) signature_url: String,
+    /// The minisign signature of the archive.
+    pub(crate) signature: String,
+}
+
+impl Index {
+    /// Builds the index of `artifacts`, published at the URLs given by the cargo-binstall
+    /// `pkg_url` template.
+    pub(crate) fn new(
+        verifying_key: &str,
+        pkg_url: &str,
+        artifacts: &[Artifact],
+    ) -> Result<Self, Error> {
+        let mut archives = Vec::with_capacity(artifacts.len());
+        for artifact in artifacts {
+            let url = artifact.url(pkg_url);
+            archives.push(IndexEntry {
+                crate_name: artifact.crate_name.clone(),
+                version: artifact.version.clone(),
+                target: artifact.target.clone(),
+                signature_url: format!("{url}.sig"),
+                url,
+                sha256: sha256_hex(&std::fs::read(&artifact.archive)?),
+                signature: std::fs::read_to_string(&artifact.signature)?,
+            });
+        }
+        archives.sort_by(|a, b| {
+            (&a.crate_name, &a.version, &a.target).cmp(&(&b.crate_name, &b.version, &b.target))
+        });
+        Ok(Self {
+            verifying_key: verifying_key.to_owned(),
+            archives,
+        })
+    }
+
+    /// Serializes the index as pretty printed JSON.
+    pub(crate) fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::Publish(Box::new(e)))
+    }
+}
+
+/// Writes the index of the most recent archive of every crate version and target in `dir` to
+/// [INDEX_FILE] in `dir`, and signs it. The index is also rendered as [HTML_FILE]. All files are
+/// replaced atomically. If the index did not change and its' signature is still valid, nothing is
+/// written.
+pub(crate) fn write_index(config: &ConfigFile, dir: &Path) -> Result<(), Error> {
+    let artifacts = collect_artifacts(dir)?;
+    let index = Index::new(&config.options.verifying_key, &config.pkg_url(), &artifacts)?;
+    write_if_changed(&dir.join(HTML_FILE), render_html(&index).as_bytes())?;
+    let json = index.to_json()?;
+    let path = dir.join(INDEX_FILE);
+    let (public_key, _) = verifying_keys(config)?;
+    if std::fs::read_to_string(&path).is_ok_and(|existing| existing == json)
+        && verify_file(&public_key, &path).is_ok()
+    {
+        debug!("{path:?} is up to date");
+        return Ok(());
+    }
+    let timestamp = iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string();
+    let trusted_comment = format!("timestamp:{timestamp}\tfile:{INDEX_FILE}");
+    let signature = sign_file(config, json.as_bytes(), Some(&trusted_comment))?;
+    write_atomically(&signature_path(&path), &signature)?;
+    write_atomically(&path, json.as_bytes())?;
+    debug!(
+        "Wrote and signed {path:?}, listing {} archives",
+        artifacts.len()
+    );
+    Ok(())
+}
+
+/// The signed index of the warehouse, as it is published: [INDEX_FILE], its' signature and
+/// [HTML_FILE], as `(file name, contents)` pairs. The index is brought up to date first.
+pub(crate) fn index_files(config: &ConfigFile) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let dir = artifact_dir(config);
+    write_index(config, &dir)?;
+    let path = dir.join(INDEX_FILE);
+    Ok(vec![
+        (INDEX_FILE.to_owned(), std::fs::read(&path)?),
+        (
+            format!("{INDEX_FILE}.sig"),
+            std::fs::read(signature_path(&path))?,
+        ),
+        (HTML_FILE.to_owned(), std::fs::read(dir.join(HTML_FILE))?),
+    ])
+}
@@ -0,0 +1,123 @@
+//! A minimal HTTP/1.1 server for testing publishers, standing in for the GitHub API or an S3
+//! compatible object storage.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    pub(crate) method: String,
+    /// The path of the request, including the query string.
+    pub(crate) path: String,
+    /// Header names are lowercase.
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl MockRequest {
+    #[must_use]
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A response of the mock server: Status code, headers and body.
+pub(crate) type MockResponse = (u16, Vec<(String, String)>, Vec<u8>);
+
+pub(crate) struct MockServer {
+    /// Base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub(crate) url: String,
+    /// Every request the server received, in order.
+    pub(crate) requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    /// Starts a server on a random local port, which answers every request with `handler`. The
+    /// server runs until the test process exits.
+    pub(crate) fn start(
+        handler: impl FnMut(&MockRequest, &str) -> MockResponse + Send + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler = Arc::new(Mutex::new(handler));
+        let (server_url, server_requests) = (url.clone(), requests.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (url, requests, handler) =
+                    (server_url.clone(), server_requests.clone(), handler.clone());
+                std::thread::spawn(move || {
+                    let _ = serve(stream, &url, &requests, &handler);
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    #[must_use]
+    pub(crate) fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    url: &str,
+    requests: &Mutex<Vec<MockRequest>>,
+    handler: &Mutex<impl FnMut(&MockRequest, &str) -> MockResponse>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        return Ok(());
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default().to_owned(),
+        parts.next().unwrap_or_default().to_owned(),
+    );
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_lowercase(), value.trim().to_owned()));
+        }
+    }
+    let length = headers
+        .iter()
+        .find(|(key, _)| key == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or_default();
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let request = MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let (status, response_headers, response_body) = (handler.lock().unwrap())(&request, url);
+    requests.lock().unwrap().push(request);
+    let mut response = format!(
+        "HTTP/1.1 {status} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response_body.len()
+    );
+    for (key, value) in response_headers {
+        response.push_str(&format!("{key}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    writer.write_all(response.as_bytes())?;
+    writer.write_all(&response_body)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,567 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+use crate::checksums::{ChecksumAlgorithm, format_checksum_file, sha256_hex};
+use crate::config::ConfigFile;
+use crate::dependencies::host_triple;
+use crate::error::Error;
+use crate::process_crates::{BuildMetadata, artifact_dir, sign_file};
+
+#[cfg(feature = "http-client")]
+pub(crate) mod github;
+pub(crate) mod html;
+pub(crate) mod index;
+#[cfg(test)]
+pub(crate) mod mock_server;
+#[cfg(feature = "http-client")]
+pub(crate) mod s3;
+pub(crate) mod static_dir;
+
+/// File name template of published archives, using the placeholders of cargo-binstall's
+/// `pkg-url`. See [Artifact::file_name].
+pub(crate) const PKG_FILE_TEMPLATE: &str = "{ name }-{ target }-v{ version }.tar";
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+/// Where `warehouseify publish` uploads the contents of the artifacts directory to. Configured in
+/// the `[publish]` section of the config file.
+pub(crate) enum PublishConfig {
+    /// One GitHub release per crate version, see [GithubConfig].
+    Github(GithubConfig),
+    /// A bucket of an S3 compatible object storage, see [S3Config].
+    S3(S3Config),
+    /// A local directory served by any web server, see [StaticConfig].
+    Static(StaticConfig),
+}
+
+impl PublishConfig {
+    /// The `pkg-url` for cargo-binstall, pointing to where this publisher uploads archives to.
+    #[must_use]
+    pub(crate) fn pkg_url(&self) -> String {
+        match self {
+            PublishConfig::Github(github) => github.pkg_url(),
+            PublishConfig::S3(s3) => s3.pkg_url(),
+            PublishConfig::Static(static_config) => static_config.pkg_url(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GithubConfig {
+    /// The repository to create releases in, as `owner/name`.
+    pub(crate) repository: String,
+    /// Base URL of the GitHub REST API.
+    #[serde(default = "default_github_api_url")]
+    pub(crate) api_url: String,
+    /// Base URL release assets are downloaded from.
+    #[serde(default = "default_github_url")]
+    pub(crate) url: String,
+    /// Name of the environment variable holding the GitHub token.
+    #[serde(default = "default_github_token_env")]
+    pub(crate) token_env: String,
+}
+
+impl GithubConfig {
+    #[must_use]
+    pub(crate) fn pkg_url(&self) -> String {
+        format!(
+            "{}/{}/releases/download/{}/{PKG_FILE_TEMPLATE}",
+            self.url.trim_end_matches('/'),
+            self.repository,
+            release_tag("{ name }", "{ version }")
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct S3Config {
+    /// Endpoint of the S3 API, e.g. `https://s3.eu-central-1.amazonaws.com`,
+    /// `http://localhost:9000` for MinIO or `https://<account>.r2.cloudflarestorage.com`.
+    pub(crate) endpoint: String,
+    pub(crate) bucket: String,
+    /// Region of the bucket. Cloudflare R2 expects `auto`.
+    #[serde(default = "default_s3_region")]
+    pub(crate) region: String,
+    /// Key of the directory the files of each crate version are uploaded into. Must contain the
+    /// `{ name }` and `{ version }` placeholders.
+    #[serde(default = "default_layout")]
+    pub(crate) layout: String,
+    /// Base URL objects are downloaded from, e.g. a CDN or the public URL of an R2 bucket.
+    /// Defaults to `<endpoint>/<bucket>`.
+    #[serde(default)]
+    pub(crate) public_url: Option<String>,
+    /// Name of the environment variable holding the access key id.
+    #[serde(default = "default_s3_access_key_id_env")]
+    pub(crate) access_key_id_env: String,
+    /// Name of the environment variable holding the secret access key.
+    #[serde(default = "default_s3_secret_access_key_env")]
+    pub(crate) secret_access_key_env: String,
+    /// Name of the environment variable holding a session token, if temporary credentials are
+    /// used.
+    #[serde(default = "default_s3_session_token_env")]
+    pub(crate) session_token_env: String,
+}
+
+impl S3Config {
+    #[must_use]
+    pub(crate) fn pkg_url(&self) -> String {
+        let base = match &self.public_url {
+            Some(url) => url.clone(),
+            None => format!("{}/{}", self.endpoint.trim_end_matches('/'), self.bucket),
+        };
+        layout_pkg_url(&base, &self.layout)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct StaticConfig {
+    /// Directory the files are written to, e.g. the document root of a web server or a network
+    /// share.
+    pub(crate) path: PathBuf,
+    /// URL the directory is served at.
+    pub(crate) base_url: String,
+    /// Path of the directory the files of each crate version are written into, relative to
+    /// `path`. Must contain the `{ name }` and `{ version }` placeholders.
+    #[serde(default = "default_layout")]
+    pub(crate) layout: String,
+}
+
+impl StaticConfig {
+    #[must_use]
+    pub(crate) fn pkg_url(&self) -> String {
+        layout_pkg_url(&self.base_url, &self.layout)
+    }
+}
+
+/// The `pkg-url` of archives published into the directories given by `layout` below `base_url`.
+#[must_use]
+pub(crate) fn layout_pkg_url(base_url: &str, layout: &str) -> String {
+    format!(
+        "{}/{}/{PKG_FILE_TEMPLATE}",
+        base_url.trim_end_matches('/'),
+        release_dir(layout, "{ name }", "{ version }")
+    )
+}
+
+fn default_github_api_url() -> String {
+    String::from("https://api.github.com")
+}
+
+fn default_github_url() -> String {
+    String::from("https://github.com")
+}
+
+fn default_github_token_env() -> String {
+    String::from("GITHUB_TOKEN")
+}
+
+fn default_s3_region() -> String {
+    String::from("us-east-1")
+}
+
+fn default_s3_access_key_id_env() -> String {
+    String::from("AWS_ACCESS_KEY_ID")
+}
+
+fn default_s3_secret_access_key_env() -> String {
+    String::from("AWS_SECRET_ACCESS_KEY")
+}
+
+fn default_s3_session_token_env() -> String {
+    String::from("AWS_SESSION_TOKEN")
+}
+
+pub(crate) fn default_layout() -> String {
+    String::from("{ name }/{ version }")
+}
+
+/// The directory the files of `version` of `crate_name` are published into, following `layout`.
+#[must_use]
+pub(crate) fn release_dir(layout: &str, crate_name: &str, version: &str) -> String {
+    layout
+        .replace("{ name }", crate_name)
+        .replace("{ version }", version)
+        .trim_matches('/')
+        .to_owned()
+}
+
+/// Returns an error, if `layout` would not give each crate version a directory of its' own.
+fn check_layout(layout: &str) -> Result<(), Error> {
+    match layout.contains("{ name }") && layout.contains("{ version }") {
+        true => Ok(()),
+        false => Err(Error::Config(
+            format!(r#"layout "{layout}" must contain "{{ name }}" and "{{ version }}""#).into(),
+        )),
+    }
+}
+
+/// The tag of the release of `version` of `crate_name`.
+#[must_use]
+pub(crate) fn release_tag(crate_name: &str, version: &str) -> String {
+    format!("{crate_name}-v{version}")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A signed archive in the artifacts directory, alongside the build metadata describing it.
+pub(crate) struct Artifact {
+    pub(crate) crate_name: String,
+    pub(crate) version: String,
+    pub(crate) target: String,
+    /// ISO 8601 timestamp of when the archive was built.
+    pub(crate) timestamp: String,
+    /// File name of the executable inside of the archive.
+    pub(crate) binary: String,
+    /// Path to the archive in the artifacts directory.
+    pub(crate) archive: PathBuf,
+    /// Path to the signature of the archive.
+    pub(crate) signature: PathBuf,
+}
+
+impl Artifact {
+    /// File name the archive is published under, following [PKG_FILE_TEMPLATE]. Unlike the name of
+    /// the archive in the artifacts directory, it does not contain the build timestamp, so that
+    /// cargo-binstall can derive it from the crate name, version and target.
+    #[must_use]
+    pub(crate) fn file_name(&self) -> String {
+        self.url(PKG_FILE_TEMPLATE)
+    }
+
+    /// The URL the archive is downloaded from, given the cargo-binstall `pkg_url` template.
+    #[must_use]
+    pub(crate) fn url(&self, pkg_url: &str) -> String {
+        pkg_url
+            .replace("{ name }", &self.crate_name)
+            .replace("{ target }", &self.target)
+            .replace("{ version }", &self.version)
+    }
+}
+
+/// Finds all archives in `dir` which have a signature and build metadata. If a crate version has
+/// been built for the same target more than once, only the most recent archive is returned.
+/// Returned artifacts are sorted by crate name, version and target.
+pub(crate) fn collect_artifacts(dir: &Path) -> Result<Vec<Artifact>, Error> {
+    let mut newest = BTreeMap::<(String, String, String), Artifact>::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(stem) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".build.toml"))
+        else {
+            continue;
+        };
+        let metadata: BuildMetadata =
+            toml::from_str(&std::fs::read_to_string(&path)?).map_err(|e| {
+                Error::Publish(format!("Malformed build metadata {path:?}: {e}").into())
+            })?;
+        let archive = dir.join(format!("{stem}.tar"));
+        let signature = dir.join(format!("{stem}.tar.sig"));
+        if !archive.is_file() || !signature.is_file() {
+            warn!("Skipping {path:?}, its' archive or the signature of the archive is missing");
+            continue;
+        }
+        let target = match metadata.target.is_empty() {
+            true => host_triple(&metadata.rustc).unwrap_or("unknown").to_owned(),
+            false => metadata.target,
+        };
+        let binary = match metadata.binary.is_empty() {
+            true => metadata.crate_name.clone(),
+            false => metadata.binary,
+        };
+        let artifact = Artifact {
+            crate_name: metadata.crate_name,
+            version: metadata.crate_version,
+            target,
+            timestamp: metadata.timestamp,
+            binary,
+            archive,
+            signature,
+        };
+        let key = (
+            artifact.crate_name.clone(),
+            artifact.version.clone(),
+            artifact.target.clone(),
+        );
+        match newest.get(&key) {
+            Some(existing) if existing.timestamp >= artifact.timestamp => {
+                debug!("Skipping {:?}, a newer build exists", artifact.archive)
+            }
+            _ => {
+                newest.insert(key, artifact);
+            }
+        }
+    }
+    Ok(newest.into_values().collect())
+}
+
+/// Groups `artifacts` by crate name and version, which is what each release consists of.
+#[must_use]
+pub(crate) fn group_releases(artifacts: &[Artifact]) -> BTreeMap<(&str, &str), Vec<&Artifact>> {
+    let mut releases = BTreeMap::<(&str, &str), Vec<&Artifact>>::new();
+    for artifact in artifacts {
+        releases
+            .entry((&artifact.crate_name, &artifact.version))
+            .or_default()
+            .push(artifact);
+    }
+    releases
+}
+
+/// The files making up the release of one crate version: Each archive and its' signature under
+/// their published names, plus one signed checksum file per configured algorithm covering the
+/// archives. Returned as `(file name, contents)` pairs. The trusted comments of the checksum files
+/// carry the timestamp of the newest archive, so that publishing the same archives again yields
+/// identical files.
+pub(crate) fn release_files(
+    config: &ConfigFile,
+    artifacts: &[&Artifact],
+) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let mut archives = Vec::with_capacity(artifacts.len());
+    let mut files = Vec::with_capacity(artifacts.len() * 2);
+    for artifact in artifacts {
+        let name = artifact.file_name();
+        let archive = std::fs::read(&artifact.archive)?;
+        files.push((format!("{name}.sig"), std::fs::read(&artifact.signature)?));
+        archives.push((name, archive));
+    }
+    let timestamp = artifacts
+        .iter()
+        .map(|artifact| artifact.timestamp.clone())
+        .max()
+        .unwrap_or_else(|| iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string());
+    let mut algorithms = config.options.checksums.clone();
+    algorithms.sort();
+    algorithms.dedup();
+    for algorithm in algorithms {
+        let contents = format_checksum_file(algorithm, &archives);
+        let trusted_comment = format!("timestamp:{timestamp}\tfile:{}", algorithm.file_name());
+        let signature = sign_file(config, contents.as_bytes(), Some(&trusted_comment))?;
+        files.push((format!("{}.sig", algorithm.file_name()), signature));
+        files.push((algorithm.file_name().to_owned(), contents.into_bytes()));
+    }
+    files.extend(archives);
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Decides whether the file `name` of a release can be skipped, given the SHA-256 digest of the
+/// already published file of the same name, if there is one. Since minisign signatures are
+/// randomized, the signature of a checksum file differs on every run. An already published one is
+/// kept, as long as the checksum file it signs is unchanged. For this, `unchanged` collects the
+/// names of all files which were found to be up to date, and release files must be checked in the
+/// order returned by [release_files].
+pub(crate) fn is_up_to_date(
+    unchanged: &mut Vec<String>,
+    name: &str,
+    contents: &[u8],
+    published_sha256: Option<&str>,
+) -> bool {
+    let up_to_date = match published_sha256 {
+        None => false,
+        Some(published) if published == sha256_hex(contents) => true,
+        Some(_) => name.strip_suffix(".sig").is_some_and(|signed| {
+            ChecksumAlgorithm::ALL
+                .iter()
+                .any(|algorithm| algorithm.file_name() == signed)
+                && unchanged.iter().any(|name| name == signed)
+        }),
+    };
+    if up_to_date {
+        unchanged.push(name.to_owned());
+    }
+    up_to_date
+}
+
+/// Writes `contents` to `path`, unless the file already has exactly these contents. Returns
+/// whether the file was written.
+pub(crate) fn write_if_changed(path: &Path, contents: &[u8]) -> Result<bool, Error> {
+    if std::fs::read(path).is_ok_and(|existing| existing == contents) {
+        debug!("{path:?} is already up to date");
+        return Ok(false);
+    }
+    write_atomically(path, contents)?;
+    Ok(true)
+}
+
+/// Writes `contents` to a temporary file next to `path` first and then renames it to `path`, so
+/// that readers never see a partially written file.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)?;
+    debug!("Wrote {path:?}");
+    Ok(())
+}
+
+/// Publishes the most recent archive of every crate version and target in the artifacts
+/// directory with the publisher configured in the `[publish]` section of the config file.
+pub(crate) fn publish(config: &ConfigFile) -> Result<(), Error> {
+    let Some(publish_config) = &config.publish else {
+        log::error!(
+            "Nothing to publish to. Configure a publisher in the [publish] section of your config file."
+        );
+        return Err(Error::Config(
+            String::from("no [publish] section in the config file").into(),
+        ));
+    };
+    let artifacts = collect_artifacts(&artifact_dir(config))?;
+    if artifacts.is_empty() {
+        warn!("There are no signed archives in the artifacts directory to publish");
+        return Ok(());
+    }
+    info!("Publishing {} archives...", artifacts.len());
+    match publish_config {
+        #[cfg(feature = "http-client")]
+        PublishConfig::Github(github) => github::publish(config, github, &artifacts),
+        #[cfg(feature = "http-client")]
+        PublishConfig::S3(s3) => {
+            check_layout(&s3.layout)?;
+            s3::publish(config, s3, &artifacts)
+        }
+        PublishConfig::Static(static_config) => {
+            check_layout(&static_config.layout)?;
+            static_dir::publish(config, static_config, &artifacts)
+        }
+        #[cfg(not(feature = "http-client"))]
+        PublishConfig::Github(_) | PublishConfig::S3(_) => Err(Error::Config(
+            String::from("publishing requires the http-client feature").into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GithubConfig, S3Config, collect_artifacts, is_up_to_date, write_if_changed};
+    use crate::checksums::sha256_hex;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn github_pkg_url_matches_published_names() {
+        let github: GithubConfig = toml::from_str(r#"repository = "o/warehouse""#).unwrap();
+        assert_eq!(
+            github.pkg_url(),
+            "https://github.com/o/warehouse/releases/download/{ name }-v{ version }/{ name }-{ target }-v{ version }.tar"
+        );
+    }
+
+    #[test]
+    fn s3_pkg_url_follows_layout() {
+        let s3: S3Config = toml::from_str(
+            r#"
+            endpoint = "http://localhost:9000/"
+            bucket = "warehouse"
+            layout = "bin/{ name }/{ version }/"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            s3.pkg_url(),
+            "http://localhost:9000/warehouse/bin/{ name }/{ version }/{ name }-{ target }-v{ version }.tar"
+        );
+    }
+
+    #[test]
+    fn keeps_signatures_of_unchanged_checksum_files() {
+        let published = sha256_hex(b"published");
+        let mut unchanged = Vec::new();
+        assert!(!is_up_to_date(
+            &mut unchanged,
+            "SHA256SUMS",
+            b"new",
+            Some(&published)
+        ));
+        assert!(!is_up_to_date(
+            &mut unchanged,
+            "SHA256SUMS.sig",
+            b"new",
+            Some(&published)
+        ));
+        assert!(is_up_to_date(
+            &mut unchanged,
+            "SHA512SUMS",
+            b"published",
+            Some(&published)
+        ));
+        assert!(is_up_to_date(
+            &mut unchanged,
+            "SHA512SUMS.sig",
+            b"new",
+            Some(&published)
+        ));
+        assert!(!is_up_to_date(
+            &mut unchanged,
+            "BLAKE3SUMS.sig",
+            b"new",
+            None
+        ));
+        // Signatures of archives are copied from the artifacts directory and compared as usual
+        assert!(is_up_to_date(
+            &mut unchanged,
+            "tool.tar",
+            b"published",
+            Some(&published)
+        ));
+        assert!(!is_up_to_date(
+            &mut unchanged,
+            "tool.tar.sig",
+            b"new",
+            Some(&published)
+        ));
+    }
+
+    #[test]
+    fn collects_newest_artifacts() {
+        let dir = TempDir::new("publish-test").unwrap();
+        for (stem, timestamp) in [
+            ("tool-old", "2025-01-01T00:00:00Z"),
+            ("tool-new", "2025-02-01T00:00:00Z"),
+        ] {
+            std::fs::write(dir.join(format!("{stem}.tar")), b"").unwrap();
+            std::fs::write(dir.join(format!("{stem}.tar.sig")), b"").unwrap();
+            std::fs::write(
+                dir.join(format!("{stem}.build.toml")),
+                format!(
+                    "crate_name = \"tool\"\ncrate_version = \"1.0.0\"\ntimestamp = \"{timestamp}\"\nrustc = \"host: x86_64-unknown-linux-gnu\"\ninherit_env = false\npassthrough_env = []\nsandboxed = false\n[env]\n"
+                ),
+            )
+            .unwrap();
+        }
+        // Build metadata without an archive is skipped
+        std::fs::copy(
+            dir.join("tool-old.build.toml"),
+            dir.join("orphan.build.toml"),
+        )
+        .unwrap();
+        let artifacts = collect_artifacts(&dir).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].archive, dir.join("tool-new.tar"));
+        assert_eq!(artifacts[0].target, "x86_64-unknown-linux-gnu");
+        assert_eq!(
+            artifacts[0].file_name(),
+            "tool-x86_64-unknown-linux-gnu-v1.0.0.tar"
+        );
+    }
+
+    #[test]
+    fn writes_only_changed_files() {
+        let dir = TempDir::new("write-test").unwrap();
+        let path = dir.join("tool/1.0.0/SHA256SUMS");
+        assert!(write_if_changed(&path, b"checksums").unwrap());
+        assert!(!write_if_changed(&path, b"checksums").unwrap());
+        assert!(write_if_changed(&path, b"new checksums").unwrap());
+        let contents = std::fs::read(&path).unwrap();
+        let entries = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(contents, b"new checksums");
+        // No temporary file is left behind
+        assert_eq!(entries, 1);
+    }
+}
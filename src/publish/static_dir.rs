@@ -0,0 +1,48 @@
+use log::{debug, info};
+
+use crate::checksums::sha256_hex;
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::publish::index::index_files;
+use crate::publish::{
+    Artifact, StaticConfig, group_releases, is_up_to_date, release_dir, release_files,
+    write_atomically, write_if_changed,
+};
+
+/// Lays out the files of each crate version in the directory given by the configured `layout`
+/// below `path`, and copies the signed index of the warehouse to the root of `path`. Files which
+/// are already up to date are not written again.
+pub(crate) fn publish(
+    config: &ConfigFile,
+    static_config: &StaticConfig,
+    artifacts: &[Artifact],
+) -> Result<(), Error> {
+    let root = &static_config.path;
+    let mut written = 0;
+    let mut total = 0;
+    for ((crate_name, version), release_artifacts) in group_releases(artifacts) {
+        let dir = root.join(release_dir(&static_config.layout, crate_name, version));
+        let mut unchanged = Vec::new();
+        for (name, contents) in release_files(config, &release_artifacts)? {
+            total += 1;
+            let path = dir.join(&name);
+            let published = std::fs::read(&path)
+                .ok()
+                .map(|existing| sha256_hex(&existing));
+            if is_up_to_date(&mut unchanged, &name, &contents, published.as_deref()) {
+                debug!("{path:?} is already up to date");
+                continue;
+            }
+            write_atomically(&path, &contents)?;
+            written += 1;
+        }
+    }
+    for (name, contents) in index_files(config)? {
+        total += 1;
+        if write_if_changed(&root.join(name), &contents)? {
+            written += 1;
+        }
+    }
+    info!("{root:?} is up to date, wrote {written} of {total} files");
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use log::{debug, info, warn};
+use minisign::{PublicKey, SecretKey};
+use serde::Serialize;
+
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::{artifact_dir, sign_file};
+use crate::publish::index::write_index;
+use crate::publish::write_atomically;
+use crate::signatures::{
+    key_id, signature_path, verify_file, verify_file_with_any, verifying_keys,
+};
+use crate::verify::{KEY_TRANSITION_PREFIX, SIGNATURE_SUFFIX, is_key_transition};
+
+#[derive(Debug, Serialize)]
+/// A statement that artifacts signed with `retired_key` are now signed with `active_key`. It is
+/// signed with the retired key, so that users who trust the retired key can trust the active one.
+struct KeyTransition {
+    /// Base64 encoded minisign public key which is no longer used.
+    retired_key: String,
+    /// Key id of the retired key.
+    retired_key_id: String,
+    /// Base64 encoded minisign public key which replaces the retired key.
+    active_key: String,
+    /// Key id of the active key.
+    active_key_id: String,
+    /// ISO 8601 timestamp of when the key was rotated.
+    timestamp: String,
+}
+
+/// Rotates the signing key of the artifacts directory from `retired_secret` to the active signing
+/// key, whose verifying key is `options.verifying_key`. First, a key transition statement signed
+/// with `retired_secret` is written to the artifacts directory. Then, every artifact which is
+/// signed with one of the `options.retired_verifying_keys` is re-signed with the active key,
+/// keeping its' trusted comment. Artifacts whose signature cannot be verified with any known key
+/// are left untouched and reported as an error.
+pub(crate) fn rotate_key(config: &ConfigFile, retired_secret: &SecretKey) -> Result<(), Error> {
+    let (active_key, retired_keys) = verifying_keys(config)?;
+    let retired_key =
+        PublicKey::from_secret_key(retired_secret).map_err(|e| Error::SigningKey(Box::new(e)))?;
+    if retired_key.keynum() == active_key.keynum() {
+        return Err(Error::Config(
+            String::from("the retired signing key is the active signing key").into(),
+        ));
+    }
+    if !retired_keys
+        .iter()
+        .any(|key| key.keynum() == retired_key.keynum())
+    {
+        return Err(Error::Config(
+            format!(
+                "the retired signing key {} is not listed in options.retired_verifying_keys",
+                key_id(&retired_key)
+            )
+            .into(),
+        ));
+    }
+    let dir = artifact_dir(config);
+    write_key_transition(&dir, retired_secret, &retired_key, &active_key)?;
+
+    let (mut resigned, mut current, mut unverified) = (0usize, 0usize, 0usize);
+    let mut names = std::fs::read_dir(&dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    names.sort();
+    for signed in names
+        .iter()
+        .filter_map(|name| name.strip_suffix(SIGNATURE_SUFFIX))
+        .filter(|signed| !is_key_transition(signed))
+    {
+        let path = dir.join(signed);
+        if !path.is_file() {
+            continue;
+        }
+        if verify_file(&active_key, &path).is_ok() {
+            debug!("{path:?} is already signed with the active key");
+            current += 1;
+            continue;
+        }
+        let trusted_comment = match verify_file_with_any(&retired_keys, &path) {
+            Ok((_, comment)) => comment,
+            Err(e) => {
+                warn!("Not re-signing {path:?}, its' signature cannot be verified: {e}");
+                unverified += 1;
+                continue;
+            }
+        };
+        let signature = sign_file(config, &std::fs::read(&path)?, Some(&trusted_comment))?;
+        write_atomically(&signature_path(&path), &signature)?;
+        debug!("Re-signed {path:?} with the active key");
+        resigned += 1;
+    }
+    info!(
+        "Re-signed {resigned} artifacts with key {}, {current} were already signed with it.",
+        key_id(&active_key)
+    );
+    // The index embeds the signatures of all archives
+    write_index(config, &dir)?;
+    match unverified {
+        0 => Ok(()),
+        _ => Err(Error::Verification(
+            format!("{unverified} artifacts could not be verified and were not re-signed").into(),
+        )),
+    }
+}
+
+/// Writes a [KeyTransition] statement from `retired_key` to `active_key` into `dir`, and signs it
+/// with `retired_secret`.
+fn write_key_transition(
+    dir: &Path,
+    retired_secret: &SecretKey,
+    retired_key: &PublicKey,
+    active_key: &PublicKey,
+) -> Result<(), Error> {
+    let timestamp = iso8601_timestamp::Timestamp::from(SystemTime::now()).to_string();
+    let statement = KeyTransition {
+        retired_key: retired_key.to_base64(),
+        retired_key_id: key_id(retired_key),
+        active_key: active_key.to_base64(),
+        active_key_id: key_id(active_key),
+        timestamp: timestamp.clone(),
+    };
+    let name = format!(
+        "{KEY_TRANSITION_PREFIX}{}-{}.toml",
+        statement.retired_key_id, statement.active_key_id
+    );
+    let contents = toml::to_string_pretty(&statement).map_err(|e| Error::Packaging(Box::new(e)))?;
+    let signature = minisign::sign(
+        Some(retired_key),
+        retired_secret,
+        contents.as_bytes(),
+        Some(&format!("timestamp:{timestamp}\tfile:{name}")),
+        None,
+    )
+    .map_err(|e| Error::Signing(Box::new(e)))?;
+    let path = dir.join(&name);
+    write_atomically(&signature_path(&path), &signature.to_bytes())?;
+    write_atomically(&path, contents.as_bytes())?;
+    info!("Wrote key transition statement {path:?}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use minisign::{KeyPair, PublicKey, SecretKey};
+
+    use super::rotate_key;
+    use crate::config::ConfigFile;
+    use crate::error::Error;
+    use crate::process_crates::artifact_dir;
+    use crate::publish::index::{INDEX_FILE, Index};
+    use crate::signatures::{signature_path, verify_file};
+    use crate::temp_dir::TempDir;
+    use crate::verify::is_key_transition;
+    use crate::{SECRET, check_minisign};
+
+    /// A config building into `workspace`, with the given active and retired verifying keys.
+    fn config(workspace: &Path, active: &PublicKey, retired: &PublicKey) -> ConfigFile {
+        toml::from_str(&format!(
+            "[crates]\n[options]\nworkspace_path = {workspace:?}\nverifying_key = \"{}\"\nretired_verifying_keys = [\"{}\"]\nautodelete_sources = false\n[dependencies]\n",
+            active.to_base64(),
+            retired.to_base64()
+        ))
+        .unwrap()
+    }
+
+    /// Writes an archive of crate `name` and its' build metadata to `dir`, signed with `secret`.
+    fn write_artifact(dir: &Path, name: &str, secret: &SecretKey) {
+        let archive = dir.join(format!("{name}.tar"));
+        std::fs::write(&archive, name).unwrap();
+        std::fs::write(
+            dir.join(format!("{name}.build.toml")),
+            format!(
+                "crate_name = \"{name}\"\ncrate_version = \"1.0.0\"\ntimestamp = \"2025-01-01T00:00:00Z\"\nrustc = \"host: x86_64-unknown-linux-gnu\"\ninherit_env = false\npassthrough_env = []\nsandboxed = false\n[env]\n"
+            ),
+        )
+        .unwrap();
+        let signature = minisign::sign(
+            None,
+            secret,
+            name.as_bytes(),
+            Some(&format!("file:{name}.tar")),
+            None,
+        )
+        .unwrap();
+        std::fs::write(signature_path(&archive), signature.to_bytes()).unwrap();
+    }
+
+    #[test]
+    fn refuses_unknown_retired_keys() {
+        let active = KeyPair::generate_unencrypted_keypair().unwrap();
+        let retired = KeyPair::generate_unencrypted_keypair().unwrap();
+        let unknown = KeyPair::generate_unencrypted_keypair().unwrap();
+        let config = config(Path::new("/nonexistent"), &active.pk, &retired.pk);
+        assert!(matches!(
+            rotate_key(&config, &active.sk),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            rotate_key(&config, &unknown.sk),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "requires minisign"]
+    fn resigns_artifacts_of_retired_key() {
+        check_minisign().unwrap();
+        let active = PublicKey::from_secret_key(
+            SECRET.get_or_init(|| KeyPair::generate_unencrypted_keypair().unwrap().sk),
+        )
+        .unwrap();
+        let retired = KeyPair::generate_unencrypted_keypair().unwrap();
+        let unknown = KeyPair::generate_unencrypted_keypair().unwrap();
+        let workspace = TempDir::new("rotate-test").unwrap();
+        let config = config(&workspace, &active, &retired.pk);
+        let dir = artifact_dir(&config);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_artifact(&dir, "old", &retired.sk);
+        write_artifact(&dir, "foreign", &unknown.sk);
+        let foreign_signature = std::fs::read(dir.join("foreign.tar.sig")).unwrap();
+
+        match rotate_key(&config, &retired.sk) {
+            Err(Error::Verification(e)) => assert!(e.to_string().starts_with("1 artifacts")),
+            result => panic!("artifact with an unknown signature was not reported: {result:?}"),
+        }
+
+        let transition = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .find(|name| is_key_transition(name) && name.ends_with(".toml"))
+            .expect("no key transition statement was written");
+        let transition = dir.join(transition);
+        verify_file(&retired.pk, &transition).unwrap();
+        let statement: toml::Table =
+            toml::from_str(&std::fs::read_to_string(&transition).unwrap()).unwrap();
+        assert_eq!(
+            statement["retired_key"].as_str(),
+            Some(&*retired.pk.to_base64())
+        );
+        assert_eq!(statement["active_key"].as_str(), Some(&*active.to_base64()));
+
+        let old = dir.join("old.tar");
+        assert_eq!(verify_file(&active, &old).unwrap(), "file:old.tar");
+        assert!(verify_file(&retired.pk, &old).is_err());
+        assert_eq!(
+            std::fs::read(dir.join("foreign.tar.sig")).unwrap(),
+            foreign_signature
+        );
+
+        let index = dir.join(INDEX_FILE);
+        verify_file(&active, &index).unwrap();
+        let index: Index = serde_json::from_str(&std::fs::read_to_string(&index).unwrap()).unwrap();
+        let entry = index
+            .archives
+            .iter()
+            .find(|entry| entry.crate_name == "old")
+            .unwrap();
+        assert_eq!(
+            entry.signature,
+            std::fs::read_to_string(signature_path(&old)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use ansi_term::Style;
+use log::{debug, error, warn};
+use minisign::{SecretKey, SecretKeyBox};
+use zeroize::Zeroizing;
+
+use crate::StdErrorS;
+use crate::cli::Args;
+use crate::config::OptionsConfig;
+use crate::error::Error;
+
+/// Environment variable holding the minisign secret key.
+pub(crate) const SECRET_ENV: &str = "WAREHOUSE_SECRET";
+/// Environment variable holding the password of the minisign secret key.
+pub(crate) const SECRET_PASSWORD_ENV: &str = "WAREHOUSE_SECRET_PASSWORD";
+
+/// Loads the minisign secret key and unlocks it with its' password. The key is taken from the
+/// first of these sources which is set:
+///
+/// 1. `--signing-key`
+/// 2. `--signing-key-file`
+/// 3. `--signing-key-fd`
+/// 4. the [SECRET_ENV] environment variable
+/// 5. `options.signing_key_file`
+/// 6. `options.signing_key`
+///
+/// The password is taken from `--signing-key-password`, `--signing-key-password-fd` or the
+/// [SECRET_PASSWORD_ENV] environment variable, in that order. If none of them is set, the password
+/// is prompted for on the terminal, without echoing it. Both environment variables are removed
+/// from the environment of warehouseify, so that they are not inherited by any builds.
+///
+/// Unencrypted secret keys do not need a password, but are refused unless
+/// `options.allow_unencrypted_signing_key` is set.
+pub(crate) fn load_signing_key(args: &Args, options: &OptionsConfig) -> Result<SecretKey, Error> {
+    let secret = read_signing_key(args, options)?;
+    unlock_secret_key(&secret, options.allow_unencrypted_signing_key, || {
+        read_password(args)
+    })
+}
+
+/// Loads a retired minisign secret key from the file at `path` for `warehouseify rotate-key`. Its'
+/// password is read from the file descriptor `password_fd`, or prompted for on the terminal.
+pub(crate) fn load_retired_signing_key(
+    path: &Path,
+    password_fd: Option<RawFd>,
+    options: &OptionsConfig,
+) -> Result<SecretKey, Error> {
+    let secret = read_key_file(path)?;
+    unlock_secret_key(&secret, options.allow_unencrypted_signing_key, || {
+        match password_fd {
+            Some(fd) => read_fd(fd, true),
+            None => prompt_password("Password for the retired minisign signing key: "),
+        }
+        .map_err(Error::SigningKey)
+    })
+}
+
+/// Decodes the minisign secret key `secret`. Encrypted keys are decrypted with the password
+/// returned by `password`, which is only called for encrypted keys. Unencrypted keys are refused,
+/// unless `allow_unencrypted` is set, in which case a warning is logged.
+fn unlock_secret_key(
+    secret: &str,
+    allow_unencrypted: bool,
+    password: impl FnOnce() -> Result<Zeroizing<String>, Error>,
+) -> Result<SecretKey, Error> {
+    let secret_box =
+        SecretKeyBox::from_string(secret).map_err(|e| Error::SigningKey(Box::new(e)))?;
+    // An empty password skips decryption, so this only succeeds for unencrypted keys: For
+    // encrypted keys, the checksum of the still encrypted key does not match.
+    if let Ok(secret_key) = secret_box.clone().into_secret_key(Some(String::new())) {
+        if !allow_unencrypted {
+            error!(
+                "The signing key is not encrypted. If this is intended, e.g. because the key is kept in the secret store of an ephemeral CI runner, set \"options.allow_unencrypted_signing_key = true\" in your configuration file."
+            );
+            return Err(Error::SigningKey(
+                String::from("refusing to use an unencrypted signing key").into(),
+            ));
+        }
+        warn!(
+            "{} The signing key is not encrypted. Anyone who can read it can sign artifacts in your name. Only use unencrypted keys on ephemeral machines and keep them in a secret store.",
+            Style::new().bold().paint("WARNING!")
+        );
+        // Not needed, but it must not be inherited by builds either
+        drop(take_env(SECRET_PASSWORD_ENV));
+        return Ok(secret_key);
+    }
+    let password = password()?;
+    secret_box
+        .into_secret_key(Some(password.to_string()))
+        .map_err(|e| Error::SigningKey(Box::new(e)))
+}
+
+/// Reads the minisign secret key from the source with the highest precedence, see
+/// [load_signing_key].
+fn read_signing_key(args: &Args, options: &OptionsConfig) -> Result<Zeroizing<String>, Error> {
+    let from_env = take_env(SECRET_ENV);
+    let secret = if let Some(secret) = &args.signing_key {
+        debug!("Using the signing key passed via --signing-key");
+        Zeroizing::new(secret.clone())
+    } else if let Some(path) = &args.signing_key_file {
+        debug!("Reading the signing key from {path:?}");
+        read_key_file(path)?
+    } else if let Some(fd) = args.signing_key_fd {
+        debug!("Reading the signing key from file descriptor {fd}");
+        read_fd(fd, false).map_err(Error::SigningKey)?
+    } else if let Some(secret) = from_env {
+        debug!("Using the signing key from ${SECRET_ENV}");
+        secret
+    } else if let Some(path) = &options.signing_key_file {
+        debug!("Reading the signing key from {path:?}");
+        read_key_file(path)?
+    } else if let Some(secret) = &options.signing_key {
+        debug!("Using the signing key from options.signing_key");
+        Zeroizing::new(secret.clone())
+    } else {
+        error!(
+            r#"You must supply a minisign signing key. Set "options.signing_key_file" or "options.signing_key" in your configuration file, set ${SECRET_ENV}, or use one of the "--signing-key", "--signing-key-file" or "--signing-key-fd" flags."#
+        );
+        return Err(Error::Config(
+            String::from("no minisign signing key supplied").into(),
+        ));
+    };
+    Ok(secret)
+}
+
+/// Reads the password of the minisign secret key from the source with the highest precedence,
+/// see [load_signing_key].
+fn read_password(args: &Args) -> Result<Zeroizing<String>, Error> {
+    let from_env = take_env(SECRET_PASSWORD_ENV);
+    if let Some(password) = &args.signing_key_password {
+        debug!("Using the signing key password passed via --signing-key-password");
+        Ok(Zeroizing::new(password.clone()))
+    } else if let Some(fd) = args.signing_key_password_fd {
+        debug!("Reading the signing key password from file descriptor {fd}");
+        read_fd(fd, true).map_err(Error::SigningKey)
+    } else if let Some(password) = from_env {
+        debug!("Using the signing key password from ${SECRET_PASSWORD_ENV}");
+        Ok(password)
+    } else {
+        prompt_password("Password for the minisign signing key: ").map_err(|e| {
+            Error::SigningKey(
+                format!("no signing key password supplied and cannot prompt for it: {e}").into(),
+            )
+        })
+    }
+}
+
+/// Reads the password for a newly generated minisign secret key from `--signing-key-password`,
+/// `--signing-key-password-fd` or the [SECRET_PASSWORD_ENV] environment variable. If none of them
+/// is set, the password is prompted for twice on the terminal. Empty passwords are refused, since
+/// they leave the key unencrypted.
+pub(crate) fn read_new_password(args: &Args) -> Result<Zeroizing<String>, Error> {
+    let password = match args.signing_key_password.is_some()
+        || args.signing_key_password_fd.is_some()
+        || std::env::var_os(SECRET_PASSWORD_ENV).is_some()
+    {
+        true => read_password(args)?,
+        false => {
+            let password = prompt_password("Password for the new minisign signing key: ")
+                .map_err(Error::SigningKey)?;
+            let repeated = prompt_password("Repeat the password: ").map_err(Error::SigningKey)?;
+            if *password != *repeated {
+                return Err(Error::SigningKey(
+                    String::from("the passwords do not match").into(),
+                ));
+            }
+            password
+        }
+    };
+    match password.is_empty() {
+        true => Err(Error::SigningKey(
+            String::from("the password of the signing key must not be empty").into(),
+        )),
+        false => Ok(password),
+    }
+}
+
+/// Reads and removes the environment variable `name`.
+fn take_env(name: &str) -> Option<Zeroizing<String>> {
+    let value = std::env::var(name).ok().map(Zeroizing::new);
+    if value.is_some() {
+        // SAFETY: Secrets are loaded before warehouseify spawns any threads, so nothing can read
+        // the environment concurrently.
+        unsafe { std::env::remove_var(name) };
+    }
+    value
+}
+
+/// Reads a minisign secret key from the file at `path`, warning if the file can be read by other
+/// users.
+fn read_key_file(path: &Path) -> Result<Zeroizing<String>, Error> {
+    let file = File::open(path).map_err(|e| {
+        Error::SigningKey(format!("cannot open signing key file {path:?}: {e}").into())
+    })?;
+    if let Ok(metadata) = file.metadata()
+        && metadata.permissions().mode() & 0o077 != 0
+    {
+        warn!(
+            "The signing key file {path:?} is accessible by other users. Consider restricting its' permissions with `chmod 600`."
+        );
+    }
+    read_to_end(file).map_err(Error::SigningKey)
+}
+
+/// Reads the file descriptor `fd`, which has been opened by the parent process, to its' end.
+/// If `first_line` is set, only the first line without its' line terminator is returned.
+fn read_fd(fd: RawFd, first_line: bool) -> Result<Zeroizing<String>, StdErrorS> {
+    // SAFETY: fcntl with F_GETFD has no memory safety preconditions. It is used to make sure the
+    // file descriptor is open, before ownership of it is taken.
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(format!("file descriptor {fd} is not open").into());
+    }
+    // SAFETY: The file descriptor is open, was handed to us by our parent process and is not used
+    // anywhere else in warehouseify.
+    let file = unsafe { File::from_raw_fd(fd) };
+    match first_line {
+        true => read_first_line(file),
+        false => read_to_end(file),
+    }
+}
+
+fn read_to_end(mut source: impl Read) -> Result<Zeroizing<String>, StdErrorS> {
+    let mut contents = Zeroizing::new(String::new());
+    source.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+fn read_first_line(source: impl Read) -> Result<Zeroizing<String>, StdErrorS> {
+    let mut line = Zeroizing::new(String::new());
+    BufReader::new(source).read_line(&mut line)?;
+    let length = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(length);
+    Ok(line)
+}
+
+/// Prompts for a password on the controlling terminal, with echoing disabled while it is typed.
+fn prompt_password(prompt: &str) -> Result<Zeroizing<String>, StdErrorS> {
+    let mut tty = File::options().read(true).write(true).open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+    // SAFETY: termios is plain old data, which tcgetattr fully initializes on success.
+    let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+    // SAFETY: fd is a valid, open file descriptor and original is a valid termios.
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let mut silent = original;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+    // SAFETY: As above.
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let password = read_first_line(&tty);
+    // SAFETY: As above. Echoing is restored, even if reading the password failed.
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    password
+}
+
+#[cfg(test)]
+mod test {
+    use minisign::KeyPair;
+
+    use super::{read_first_line, unlock_secret_key};
+    use crate::error::Error;
+
+    #[test]
+    fn refuses_unencrypted_keys_unless_allowed() {
+        // An empty password leaves the key unencrypted, but, unlike
+        // generate_unencrypted_keypair, writes its' checksum
+        let keypair = KeyPair::generate_encrypted_keypair(Some(String::new())).unwrap();
+        let secret = keypair.sk.to_box(None).unwrap().to_string();
+        let no_password = || -> Result<_, Error> { panic!("unencrypted keys need no password") };
+        assert!(matches!(
+            unlock_secret_key(&secret, false, no_password),
+            Err(Error::SigningKey(_))
+        ));
+        let secret_key = unlock_secret_key(&secret, true, no_password).unwrap();
+        assert_eq!(secret_key.keynum(), keypair.sk.keynum());
+    }
+
+    #[test]
+    fn reads_password_without_line_terminator() {
+        assert_eq!(
+            read_first_line(b"Correct-Horse\r\nBattery-Staple\n".as_slice())
+                .unwrap()
+                .as_str(),
+            "Correct-Horse"
+        );
+        assert_eq!(read_first_line(b"".as_slice()).unwrap().as_str(), "");
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::artifact_dir;
+use crate::process_crates::edit_sources::binstall_metadata;
+use crate::publish::{Artifact, collect_artifacts, default_layout, layout_pkg_url, release_dir};
+
+/// Directory inside of the workspace, which the manifests for `cargo binstall --manifest-path`
+/// are written to.
+const MANIFEST_DIR: &str = "serve/";
+
+/// Maps request paths to the files they are answered with: The most recent archive of every crate
+/// version and target and its' signature under the names they are published with, laid out like
+/// the `static` publisher does by default, and every file in `dir` under its' own name.
+fn routes(dir: &Path, artifacts: &[Artifact]) -> Result<HashMap<String, PathBuf>, Error> {
+    let mut routes = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            routes.insert(format!("/{name}"), path.clone());
+        }
+    }
+    for artifact in artifacts {
+        let release = release_dir(&default_layout(), &artifact.crate_name, &artifact.version);
+        let name = artifact.file_name();
+        routes.insert(format!("/{release}/{name}"), artifact.archive.clone());
+        routes.insert(format!("/{release}/{name}.sig"), artifact.signature.clone());
+    }
+    Ok(routes)
+}
+
+/// A minimal Cargo.toml for the crate of `artifact`, which only exists to be passed to
+/// `cargo binstall --manifest-path`. Its' binstall metadata is the same `edit_sources` writes into
+/// the Cargo.toml of each crate, except for `pkg_url`.
+pub(crate) fn manifest(
+    artifact: &Artifact,
+    pkg_url: &str,
+    verifying_key: &str,
+) -> Result<String, Error> {
+    let mut metadata = toml::value::Table::new();
+    metadata.insert(
+        String::from("binstall"),
+        toml::Value::Table(binstall_metadata(pkg_url, verifying_key)),
+    );
+    let mut package = toml::value::Table::new();
+    package.insert(
+        String::from("name"),
+        toml::Value::String(artifact.crate_name.clone()),
+    );
+    package.insert(
+        String::from("version"),
+        toml::Value::String(artifact.version.clone()),
+    );
+    package.insert(
+        String::from("edition"),
+        toml::Value::String(String::from("2021")),
+    );
+    package.insert(String::from("metadata"), toml::Value::Table(metadata));
+    let mut bin = toml::value::Table::new();
+    let bin_name = artifact
+        .binary
+        .strip_suffix(".exe")
+        .unwrap_or(&artifact.binary);
+    bin.insert(
+        String::from("name"),
+        toml::Value::String(bin_name.to_owned()),
+    );
+    bin.insert(
+        String::from("path"),
+        toml::Value::String(String::from("src/main.rs")),
+    );
+    let mut root = toml::value::Table::new();
+    root.insert(String::from("package"), toml::Value::Table(package));
+    root.insert(
+        String::from("bin"),
+        toml::Value::Array(vec![toml::Value::Table(bin)]),
+    );
+    toml::to_string(&root).map_err(|e| Error::Packaging(Box::new(e)))
+}
+
+/// The most recent version of each crate in `artifacts`.
+pub(crate) fn newest_versions(artifacts: &[Artifact]) -> Vec<&Artifact> {
+    let mut newest = BTreeMap::<&str, &Artifact>::new();
+    for artifact in artifacts {
+        let version = |artifact: &Artifact| semver::Version::parse(&artifact.version).ok();
+        match newest.get(artifact.crate_name.as_str()) {
+            Some(existing) if version(existing) >= version(artifact) => (),
+            _ => {
+                newest.insert(&artifact.crate_name, artifact);
+            }
+        }
+    }
+    newest.into_values().collect()
+}
+
+/// Serves the artifacts directory over HTTP on `bind`:`port`, until the process is terminated.
+/// For the most recent version of each crate, a Cargo.toml pointing cargo-binstall to this server
+/// is written to the workspace, and a `cargo binstall --manifest-path` command installing the
+/// crate from it is printed to stdout.
+pub(crate) fn serve(config: &ConfigFile, bind: &str, port: u16) -> Result<(), Error> {
+    let dir = artifact_dir(config);
+    let artifacts = collect_artifacts(&dir)?;
+    let listener = TcpListener::bind((bind, port))?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    let pkg_url = layout_pkg_url(&base_url, &default_layout());
+    if artifacts.is_empty() {
+        warn!("There are no signed archives in {dir:?} to install");
+    }
+    for artifact in newest_versions(&artifacts) {
+        let manifest_dir = config
+            .options
+            .workspace_path
+            .join(MANIFEST_DIR)
+            .join(&artifact.crate_name);
+        std::fs::create_dir_all(manifest_dir.join("src"))?;
+        std::fs::write(manifest_dir.join("src/main.rs"), "fn main() {}\n")?;
+        let manifest_path = std::path::absolute(manifest_dir.join("Cargo.toml"))?;
+        std::fs::write(
+            &manifest_path,
+            manifest(artifact, &pkg_url, &config.options.verifying_key)?,
+        )?;
+        debug!("Wrote {manifest_path:?}");
+        println!(
+            "cargo binstall --manifest-path {} {}@{}",
+            manifest_path.display(),
+            artifact.crate_name,
+            artifact.version
+        );
+    }
+    info!("Serving {dir:?} at {base_url}, press Ctrl+C to stop");
+    run(listener, routes(&dir, &artifacts)?);
+    Ok(())
+}
+
+/// Serves `artifacts` and the files in `dir` on a free port on localhost, in the background, until
+/// the process exits. Returns the base URL of the server.
+pub(crate) fn start_local_server(dir: &Path, artifacts: &[Artifact]) -> Result<String, Error> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    let routes = routes(dir, artifacts)?;
+    std::thread::spawn(move || run(listener, routes));
+    debug!("Serving {dir:?} at {base_url}");
+    Ok(base_url)
+}
+
+/// Answers every connection to `listener` on a thread of its' own.
+fn run(listener: TcpListener, routes: HashMap<String, PathBuf>) {
+    let routes = Arc::new(routes);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {e}");
+                continue;
+            }
+        };
+        let routes = routes.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = respond(stream, &routes) {
+                debug!("Failed to answer a request: {e}");
+            }
+        });
+    }
+}
+
+/// Answers a single `GET` or `HEAD` request with the file `routes` maps its' path to.
+fn respond(stream: TcpStream, routes: &HashMap<String, PathBuf>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The request headers are not needed
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = match path {
+        "/" => "/index.html",
+        path => path,
+    };
+    let (status, content_type, body) = match (method, routes.get(path)) {
+        ("GET" | "HEAD", Some(file)) => {
+            let content_type = match file.extension().and_then(|extension| extension.to_str()) {
+                Some("html") => "text/html; charset=utf-8",
+                Some("json") => "application/json",
+                Some("toml") | Some("sig") => "text/plain; charset=utf-8",
+                _ => "application/octet-stream",
+            };
+            match std::fs::read(file) {
+                Ok(contents) => ("200 OK", content_type, contents),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    warn!("{file:?} was removed while serving it");
+                    ("404 Not Found", "text/plain", b"not found\n".to_vec())
+                }
+                Err(e) => {
+                    warn!("Failed to read {file:?}: {e}");
+                    (
+                        "500 Internal Server Error",
+                        "text/plain",
+                        b"internal server error\n".to_vec(),
+                    )
+                }
+            }
+        }
+        ("GET" | "HEAD", None) => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed\n".to_vec(),
+        ),
+    };
+    info!("{method} {target} {status}");
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    if method != "HEAD" {
+        writer.write_all(&body)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::{manifest, routes, run};
+    use crate::publish::Artifact;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn serves_published_names() {
+        let dir = TempDir::new("serve-test").unwrap();
+        std::fs::write(dir.join("tool-20250101.tar"), b"archive").unwrap();
+        std::fs::write(dir.join("tool-20250101.tar.sig"), b"signature").unwrap();
+        let artifact = Artifact {
+            crate_name: String::from("tool"),
+            version: String::from("1.0.0"),
+            target: String::from("x86_64-unknown-linux-gnu"),
+            timestamp: String::new(),
+            binary: String::from("tl"),
+            archive: dir.join("tool-20250101.tar"),
+            signature: dir.join("tool-20250101.tar.sig"),
+        };
+        let routes = routes(&dir, std::slice::from_ref(&artifact)).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || run(listener, routes));
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let archive = get("/tool/1.0.0/tool-x86_64-unknown-linux-gnu-v1.0.0.tar");
+        let signature = get("/tool/1.0.0/tool-x86_64-unknown-linux-gnu-v1.0.0.tar.sig");
+        let flat = get("/tool-20250101.tar");
+        let missing = get("/tool/1.0.0/tool-aarch64-apple-darwin-v1.0.0.tar");
+        assert!(archive.starts_with("HTTP/1.1 200 OK") && archive.ends_with("\r\n\r\narchive"));
+        assert!(signature.ends_with("\r\n\r\nsignature"));
+        assert!(flat.ends_with("\r\n\r\narchive"));
+        assert!(missing.starts_with("HTTP/1.1 404"));
+
+        // Files which can no longer be read are not answered with an empty body
+        std::fs::remove_file(dir.join("tool-20250101.tar.sig")).unwrap();
+        std::fs::remove_file(dir.join("tool-20250101.tar")).unwrap();
+        std::fs::create_dir(dir.join("tool-20250101.tar")).unwrap();
+        let removed = get("/tool/1.0.0/tool-x86_64-unknown-linux-gnu-v1.0.0.tar.sig");
+        let unreadable = get("/tool/1.0.0/tool-x86_64-unknown-linux-gnu-v1.0.0.tar");
+        assert!(removed.starts_with("HTTP/1.1 404"));
+        assert!(unreadable.starts_with("HTTP/1.1 500"));
+
+        let manifest: toml::Value =
+            toml::from_str(&manifest(&artifact, "http://127.0.0.1/{ name }", "RWQkey").unwrap())
+                .unwrap();
+        assert_eq!(manifest["bin"][0]["name"].as_str(), Some("tl"));
+        assert_eq!(
+            manifest["package"]["metadata"]["binstall"]["pkg-url"].as_str(),
+            Some("http://127.0.0.1/{ name }")
+        );
+        assert_eq!(
+            manifest["package"]["metadata"]["binstall"]["signing"]["pubkey"].as_str(),
+            Some("RWQkey")
+        );
+    }
+}
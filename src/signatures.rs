@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use minisign::{PublicKey, SignatureBox};
+
+use crate::StdErrorS;
+use crate::config::ConfigFile;
+use crate::error::Error;
+
+/// The trusted comment template used if `options.trusted_comment` is not set. Like minisign's
+/// own default trusted comment, it is a tab-separated list of `key:value` pairs.
+pub(crate) const DEFAULT_TRUSTED_COMMENT: &str = "timestamp:{timestamp}\tfile:{file}\tcrate:{crate}\tversion:{version}\ttarget:{target}\tsource_sha256:{source_sha256}";
+
+/// Rendered in place of a placeholder whose value is not known, such as the source checksum of a
+/// crate which was not downloaded.
+const UNKNOWN_VALUE: &str = "none";
+
+/// Values which can be embedded into the trusted comment of an archive signature. Each field
+/// replaces the placeholder of the same name in a trusted comment template, e.g. `{crate}`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrustedCommentFields<'a> {
+    /// `{file}`: File name of the signed archive.
+    pub(crate) file: &'a str,
+    /// `{crate}`: Package name of the crate.
+    pub(crate) crate_name: &'a str,
+    /// `{version}`: Package version of the crate.
+    pub(crate) version: &'a str,
+    /// `{target}`: Target triple the crate was built for.
+    pub(crate) target: Option<&'a str>,
+    /// `{timestamp}`: ISO 8601 timestamp of when the crate was built.
+    pub(crate) timestamp: &'a str,
+    /// `{source_sha256}`: SHA-256 digest of the downloaded crate source.
+    pub(crate) source_sha256: Option<&'a str>,
+}
+
+/// Replaces all placeholders in `template` with the values in `fields`. Returns an error, if the
+/// rendered comment spans multiple lines, which minisign does not support.
+pub(crate) fn render_trusted_comment(
+    template: &str,
+    fields: &TrustedCommentFields,
+) -> Result<String, StdErrorS> {
+    let comment = [
+        ("{file}", fields.file),
+        ("{crate}", fields.crate_name),
+        ("{version}", fields.version),
+        ("{target}", fields.target.unwrap_or(UNKNOWN_VALUE)),
+        ("{timestamp}", fields.timestamp),
+        (
+            "{source_sha256}",
+            fields.source_sha256.unwrap_or(UNKNOWN_VALUE),
+        ),
+    ]
+    .into_iter()
+    .fold(template.to_string(), |comment, (placeholder, value)| {
+        comment.replace(placeholder, value)
+    });
+    if comment.contains(['\n', '\r']) {
+        return Err(format!("Trusted comments must be a single line, got {comment:?}").into());
+    }
+    Ok(comment)
+}
+
+/// Splits a trusted comment into its' tab-separated `key:value` pairs. Parts of the comment which
+/// are not `key:value` pairs are skipped.
+#[must_use]
+pub(crate) fn parse_trusted_comment(comment: &str) -> Vec<(&str, &str)> {
+    comment
+        .split('\t')
+        .filter_map(|part| part.split_once(':'))
+        .collect()
+}
+
+/// The path of the minisign signature belonging to `file`.
+#[must_use]
+pub(crate) fn signature_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Environment variable holding the verifying key `options.verifying_key` is expected to be, like
+/// the repository variable of the same name in the workflow of `warehouseify init --github-actions`.
+pub(crate) const PUBLIC_ENV: &str = "WAREHOUSE_PUBLIC";
+
+/// Errors, if the [PUBLIC_ENV] environment variable is set, but does not hold the same verifying
+/// key as `options.verifying_key`. An empty variable is treated like an unset one, as GitHub Actions
+/// expands repository variables that don't exist to an empty string.
+pub(crate) fn check_public_env(config: &ConfigFile) -> Result<(), Error> {
+    match std::env::var(PUBLIC_ENV) {
+        Ok(expected) if expected.trim().is_empty() => {
+            warn!(
+                "{PUBLIC_ENV} is empty, so options.verifying_key is not checked against it. Set the repository variable {PUBLIC_ENV} to the verifying key to enable the check"
+            );
+            Ok(())
+        }
+        Ok(expected) => check_public_key(&config.options.verifying_key, &expected),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Errors, unless `configured` and `expected` are the same verifying key. Both are parsed first,
+/// so that only the keys themselves are compared.
+fn check_public_key(configured: &str, expected: &str) -> Result<(), Error> {
+    let configured = PublicKey::from_base64(configured).map_err(|e| {
+        Error::Config(format!("Malformed verifying key {configured:?}: {e}").into())
+    })?;
+    let expected = PublicKey::from_base64(expected.trim()).map_err(|e| {
+        Error::Config(format!("Malformed verifying key in {PUBLIC_ENV}: {e}").into())
+    })?;
+    match configured.to_bytes() == expected.to_bytes() {
+        true => Ok(()),
+        false => Err(Error::Config(
+            format!(
+                "options.verifying_key {} does not match the verifying key {} in {PUBLIC_ENV}",
+                key_id(&configured),
+                key_id(&expected)
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Parses the active `verifying_key` and all `retired_verifying_keys` from the config file.
+pub(crate) fn verifying_keys(config: &ConfigFile) -> Result<(PublicKey, Vec<PublicKey>), Error> {
+    let parse = |key: &str| {
+        PublicKey::from_base64(key)
+            .map_err(|e| Error::Config(format!("Malformed verifying key {key:?}: {e}").into()))
+    };
+    let active = parse(&config.options.verifying_key)?;
+    let retired = config
+        .options
+        .retired_verifying_keys
+        .iter()
+        .map(|key| parse(key))
+        .collect::<Result<Vec<PublicKey>, Error>>()?;
+    Ok((active, retired))
+}
+
+/// Verifies `file` with each of `public_keys` in turn, see [verify_file]. Returns the key which
+/// verified the file alongside the trusted comment, or the error of the last attempt.
+pub(crate) fn verify_file_with_any<'a>(
+    public_keys: &'a [PublicKey],
+    file: &Path,
+) -> Result<(&'a PublicKey, String), StdErrorS> {
+    let mut last_error: StdErrorS = String::from("no verifying keys configured").into();
+    for public_key in public_keys {
+        match verify_file(public_key, file) {
+            Ok(comment) => return Ok((public_key, comment)),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// The key id of `public_key`, formatted like minisign does.
+#[must_use]
+pub(crate) fn key_id(public_key: &PublicKey) -> String {
+    // minisign prints the little endian key number as an integer
+    public_key
+        .keynum()
+        .iter()
+        .rev()
+        .map(|byte| format!("{byte:02X}"))
+        .collect()
+}
+
+/// Verifies `file` against its' signature at [signature_path] with `public_key`, including the
+/// signature of the trusted comment. Returns the verified trusted comment.
+pub(crate) fn verify_file(public_key: &PublicKey, file: &Path) -> Result<String, StdErrorS> {
+    let signature_box = SignatureBox::from_file(signature_path(file))?;
+    minisign::verify(
+        public_key,
+        &signature_box,
+        File::open(file)?,
+        true,
+        false,
+        false,
+    )?;
+    Ok(signature_box.trusted_comment()?)
+}
+
+#[cfg(test)]
+mod test {
+    use minisign::KeyPair;
+
+    use super::{
+        DEFAULT_TRUSTED_COMMENT, TrustedCommentFields, check_public_key, parse_trusted_comment,
+        render_trusted_comment, signature_path, verify_file,
+    };
+    use crate::error::Error;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn renders_and_parses_default_trusted_comment() {
+        let fields = TrustedCommentFields {
+            file: "tool-1.0.0.tar",
+            crate_name: "tool",
+            version: "1.0.0",
+            target: Some("x86_64-unknown-linux-gnu"),
+            timestamp: "2025-01-01T00:00:00Z",
+            source_sha256: None,
+        };
+        let comment = render_trusted_comment(DEFAULT_TRUSTED_COMMENT, &fields).unwrap();
+        assert_eq!(
+            parse_trusted_comment(&comment),
+            [
+                ("timestamp", "2025-01-01T00:00:00Z"),
+                ("file", "tool-1.0.0.tar"),
+                ("crate", "tool"),
+                ("version", "1.0.0"),
+                ("target", "x86_64-unknown-linux-gnu"),
+                ("source_sha256", "none"),
+            ]
+        );
+        assert!(render_trusted_comment("crate:{crate}\nevil", &fields).is_err());
+    }
+
+    #[test]
+    fn compares_parsed_verifying_keys() {
+        let key = KeyPair::generate_unencrypted_keypair()
+            .unwrap()
+            .pk
+            .to_base64();
+        let other = KeyPair::generate_unencrypted_keypair()
+            .unwrap()
+            .pk
+            .to_base64();
+        assert!(check_public_key(&key, &key).is_ok());
+        // Repository variables are often saved with a trailing newline
+        assert!(check_public_key(&key, &format!("{key}\n")).is_ok());
+        for expected in [other.as_str(), "", "verifying_key = \"RWQ\""] {
+            assert!(matches!(
+                check_public_key(&key, expected),
+                Err(Error::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn verifies_trusted_comment() {
+        let keypair = KeyPair::generate_unencrypted_keypair().unwrap();
+        let dir = TempDir::new("signatures-test").unwrap();
+        let file = dir.join("tool.tar");
+        std::fs::write(&file, b"archive").unwrap();
+        let signature = minisign::sign(
+            Some(&keypair.pk),
+            &keypair.sk,
+            b"archive".as_slice(),
+            Some("crate:tool"),
+            None,
+        )
+        .unwrap();
+        std::fs::write(signature_path(&file), signature.to_bytes()).unwrap();
+        assert_eq!(verify_file(&keypair.pk, &file).unwrap(), "crate:tool");
+        std::fs::write(&file, b"tampered").unwrap();
+        assert!(verify_file(&keypair.pk, &file).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::debug;
+
+/// Number of [TempDir]s created by this process so far, which keeps their' names unique.
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A uniquely named directory inside of [std::env::temp_dir], which is removed together with
+/// its' contents once it is dropped. This also happens when unwinding from a panic, so failing
+/// tests don't leave their' files behind.
+#[derive(Debug)]
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a new, empty directory named `warehouseify-<prefix>-<pid>-<n>`.
+    pub(crate) fn new(prefix: &str) -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "warehouseify-{prefix}-{}-{}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            debug!("Could not remove {:?}: {e}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TempDir;
+
+    #[test]
+    fn removes_directory_on_drop() {
+        let dir = TempDir::new("temp-dir-test").unwrap();
+        let other = TempDir::new("temp-dir-test").unwrap();
+        assert_ne!(*dir, *other);
+        std::fs::write(dir.join("file"), b"contents").unwrap();
+        let path = dir.to_path_buf();
+        drop(dir);
+        assert!(!path.exists());
+        assert!(other.exists());
+    }
+}
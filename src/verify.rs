@@ -0,0 +1,477 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use log::{debug, error};
+use minisign::PublicKey;
+
+use crate::checksums::{ARCHIVE_EXTENSION, ChecksumAlgorithm, parse_checksum_file, sha256_hex};
+use crate::config::ConfigFile;
+use crate::error::Error;
+use crate::process_crates::artifact_dir;
+use crate::publish::html::HTML_FILE;
+use crate::publish::index::{INDEX_FILE, Index};
+use crate::signatures::{
+    key_id, parse_trusted_comment, verify_file, verify_file_with_any, verifying_keys,
+};
+
+/// Suffix of the build metadata file written next to each archive.
+const METADATA_SUFFIX: &str = ".build.toml";
+/// Suffix of minisign signature files.
+pub(crate) const SIGNATURE_SUFFIX: &str = ".sig";
+/// Prefix of the file name of key transition statements, see [crate::rotate].
+pub(crate) const KEY_TRANSITION_PREFIX: &str = "key-transition-";
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something which is wrong with a file in an artifacts directory.
+pub(crate) struct Discrepancy {
+    pub(crate) path: PathBuf,
+    pub(crate) problem: String,
+}
+
+impl Discrepancy {
+    fn new(path: PathBuf, problem: impl Into<String>) -> Self {
+        Self {
+            path,
+            problem: problem.into(),
+        }
+    }
+}
+
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.problem)
+    }
+}
+
+/// Verifies `path` with the `verifying_key` from the config file. If `path` is a directory, or
+/// not given, which means the artifacts directory, every file in it is checked, see
+/// [check_directory]. Otherwise, `path` is verified as a single archive and the authenticated
+/// contents of its' trusted comment are printed.
+pub(crate) fn verify(config: &ConfigFile, path: Option<&Path>) -> Result<(), Error> {
+    let (public_key, retired_keys) = verifying_keys(config)?;
+    let path = path.map_or_else(|| artifact_dir(config), Path::to_path_buf);
+    if path.is_dir() {
+        verify_directory(&public_key, &retired_keys, &path)
+    } else {
+        verify_archive(&public_key, &path)
+    }
+}
+
+/// Verifies the archive at `path` against its' signature and prints the authenticated contents
+/// of its' trusted comment.
+fn verify_archive(public_key: &PublicKey, path: &Path) -> Result<(), Error> {
+    let trusted_comment = match verify_file(public_key, path) {
+        Ok(comment) => comment,
+        Err(e) => {
+            error!("Could not verify {path:?}: {e}");
+            return Err(Error::Verification(e));
+        }
+    };
+    println!("{}: signature verified", path.display());
+    let fields = parse_trusted_comment(&trusted_comment);
+    if fields.is_empty() {
+        println!("  trusted comment: {trusted_comment}");
+    }
+    let key_width = fields
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or_default();
+    for (key, value) in fields {
+        println!("  {key:<key_width$}  {value}");
+    }
+    Ok(())
+}
+
+/// Checks every file in `dir` with [check_directory] and prints all discrepancies found.
+fn verify_directory(
+    public_key: &PublicKey,
+    retired_keys: &[PublicKey],
+    dir: &Path,
+) -> Result<(), Error> {
+    let (verified, discrepancies) = check_directory(public_key, retired_keys, dir)?;
+    for discrepancy in discrepancies.iter() {
+        println!("{discrepancy}");
+    }
+    println!(
+        "{} archives verified, {} discrepancies found in {}.",
+        verified.len(),
+        discrepancies.len(),
+        dir.display()
+    );
+    match discrepancies.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Verification(
+            format!(
+                "{} discrepancies found in {}",
+                discrepancies.len(),
+                dir.display()
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Checks an artifacts directory for discrepancies:
+///
+/// - Every archive must have a valid signature made with `public_key`. The `file`, `crate` and
+///   `version` fields of its' trusted comment must match the archive and its' build metadata.
+/// - Every checksum file must have a valid signature, every file it lists must exist and match its'
+///   checksum, and every archive must be listed in it.
+/// - Every key transition statement must have a valid signature made with one of `retired_keys`.
+/// - The index must have a valid signature, and every archive it lists must exist.
+/// - There must be no orphaned signatures or build metadata, and no unexpected files.
+///
+/// Artifacts signed with one of `retired_keys` instead of `public_key` are reported as well.
+/// Returns the paths of all archives which were verified successfully, and all discrepancies.
+pub(crate) fn check_directory(
+    public_key: &PublicKey,
+    retired_keys: &[PublicKey],
+    dir: &Path,
+) -> std::io::Result<(Vec<PathBuf>, Vec<Discrepancy>)> {
+    let mut names = BTreeSet::new();
+    let mut discrepancies = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        match entry.file_type()?.is_file() {
+            true => names.insert(name),
+            false => {
+                discrepancies.push(Discrepancy::new(entry.path(), "unexpected directory"));
+                continue;
+            }
+        };
+    }
+    let is_archive = |name: &str| {
+        Path::new(name)
+            .extension()
+            .is_some_and(|extension| extension == ARCHIVE_EXTENSION)
+    };
+    let archives = names
+        .iter()
+        .filter(|name| is_archive(name))
+        .cloned()
+        .collect::<Vec<String>>();
+    let checksum_files = ChecksumAlgorithm::ALL
+        .into_iter()
+        .filter(|algorithm| names.contains(algorithm.file_name()))
+        .collect::<Vec<ChecksumAlgorithm>>();
+
+    let mut verified = Vec::new();
+    for archive in archives.iter() {
+        let path = dir.join(archive);
+        debug!("Verifying {path:?}");
+        if !names.contains(&format!("{archive}{SIGNATURE_SUFFIX}")) {
+            discrepancies.push(Discrepancy::new(path, "archive is not signed"));
+            continue;
+        }
+        let trusted_comment = match verify_file(public_key, &path) {
+            Ok(comment) => comment,
+            Err(e) => {
+                discrepancies.push(signature_discrepancy(path, retired_keys, e));
+                continue;
+            }
+        };
+        let problems = check_trusted_comment(dir, archive, &trusted_comment);
+        match problems.is_empty() {
+            true => verified.push(path),
+            false => discrepancies.extend(
+                problems
+                    .into_iter()
+                    .map(|problem| Discrepancy::new(path.clone(), problem)),
+            ),
+        }
+    }
+
+    for algorithm in checksum_files {
+        let path = dir.join(algorithm.file_name());
+        debug!("Verifying {path:?}");
+        if !names.contains(&format!("{}{SIGNATURE_SUFFIX}", algorithm.file_name())) {
+            discrepancies.push(Discrepancy::new(
+                path.clone(),
+                "checksum file is not signed",
+            ));
+        } else if let Err(e) = verify_file(public_key, &path) {
+            discrepancies.push(signature_discrepancy(path.clone(), retired_keys, e));
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let listed = parse_checksum_file(&contents);
+        for (digest, name) in listed.iter() {
+            if !names.contains(*name) {
+                discrepancies.push(Discrepancy::new(
+                    dir.join(name),
+                    format!("listed in {}, but missing", algorithm.file_name()),
+                ));
+            } else if algorithm.digest_hex(&std::fs::read(dir.join(name))?) != *digest {
+                discrepancies.push(Discrepancy::new(
+                    dir.join(name),
+                    format!("does not match its' checksum in {}", algorithm.file_name()),
+                ));
+            }
+        }
+        for archive in archives.iter() {
+            if !listed.iter().any(|(_, name)| name == archive) {
+                discrepancies.push(Discrepancy::new(
+                    dir.join(archive),
+                    format!("not covered by {}", algorithm.file_name()),
+                ));
+            }
+        }
+    }
+
+    for name in names
+        .iter()
+        .filter(|name| is_key_transition(name) && !name.ends_with(SIGNATURE_SUFFIX))
+    {
+        let path = dir.join(name);
+        debug!("Verifying {path:?}");
+        if let Err(e) = verify_file_with_any(retired_keys, &path) {
+            discrepancies.push(Discrepancy::new(
+                path,
+                format!("key transition statement is not signed by a retired key: {e}"),
+            ));
+        }
+    }
+
+    if names.contains(INDEX_FILE) {
+        discrepancies.extend(check_index(
+            public_key,
+            retired_keys,
+            dir,
+            &names,
+            &archives,
+        )?);
+    }
+
+    for name in names.iter() {
+        let problem = if let Some(signed) = name.strip_suffix(SIGNATURE_SUFFIX) {
+            (!names.contains(signed)).then_some("orphaned signature")
+        } else if let Some(stem) = name.strip_suffix(METADATA_SUFFIX) {
+            (!names.contains(&format!("{stem}.{ARCHIVE_EXTENSION}")))
+                .then_some("orphaned build metadata")
+        } else if is_archive(name)
+            || is_key_transition(name)
+            || name == INDEX_FILE
+            || name == HTML_FILE
+            || ChecksumAlgorithm::ALL
+                .iter()
+                .any(|algorithm| algorithm.file_name() == name)
+        {
+            None
+        } else {
+            Some("unexpected file")
+        };
+        if let Some(problem) = problem {
+            discrepancies.push(Discrepancy::new(dir.join(name), problem));
+        }
+    }
+    Ok((verified, discrepancies))
+}
+
+/// Checks the signature of the [INDEX_FILE] in `dir`, and that an archive matching the checksum
+/// of every entry is among `archives`.
+fn check_index(
+    public_key: &PublicKey,
+    retired_keys: &[PublicKey],
+    dir: &Path,
+    names: &BTreeSet<String>,
+    archives: &[String],
+) -> std::io::Result<Vec<Discrepancy>> {
+    let path = dir.join(INDEX_FILE);
+    debug!("Verifying {path:?}");
+    let mut discrepancies = Vec::new();
+    if !names.contains(&format!("{INDEX_FILE}{SIGNATURE_SUFFIX}")) {
+        discrepancies.push(Discrepancy::new(path.clone(), "index is not signed"));
+    } else if let Err(e) = verify_file(public_key, &path) {
+        discrepancies.push(signature_discrepancy(path.clone(), retired_keys, e));
+    }
+    let index = match serde_json::from_slice::<Index>(&std::fs::read(&path)?) {
+        Ok(index) => index,
+        Err(e) => {
+            discrepancies.push(Discrepancy::new(path, format!("malformed index: {e}")));
+            return Ok(discrepancies);
+        }
+    };
+    let mut digests = BTreeSet::new();
+    for archive in archives {
+        digests.insert(sha256_hex(&std::fs::read(dir.join(archive))?));
+    }
+    for entry in index.archives {
+        if !digests.contains(&entry.sha256) {
+            discrepancies.push(Discrepancy::new(
+                path.clone(),
+                format!(
+                    "lists {} {} for {}, but no archive matches its' checksum",
+                    entry.crate_name, entry.version, entry.target
+                ),
+            ));
+        }
+    }
+    Ok(discrepancies)
+}
+
+/// Whether `name` is the file name of a key transition statement or its' signature.
+#[must_use]
+pub(crate) fn is_key_transition(name: &str) -> bool {
+    name.starts_with(KEY_TRANSITION_PREFIX)
+}
+
+/// Describes why the signature of `path` could not be verified with the active key. If one of
+/// `retired_keys` verifies it, the artifact merely has to be re-signed.
+fn signature_discrepancy(
+    path: PathBuf,
+    retired_keys: &[PublicKey],
+    error: crate::StdErrorS,
+) -> Discrepancy {
+    match verify_file_with_any(retired_keys, &path) {
+        Ok((retired_key, _)) => Discrepancy::new(
+            path,
+            format!(
+                "signed with retired key {}, re-sign it with `warehouseify rotate-key`",
+                key_id(retired_key)
+            ),
+        ),
+        Err(_) => Discrepancy::new(path, format!("signature verification failed: {error}")),
+    }
+}
+
+/// Compares the `file`, `crate` and `version` fields of the verified `trusted_comment` of
+/// `archive` with its' file name and its' build metadata, if any. Fields missing from the trusted
+/// comment are not checked. Returns a description of each mismatch.
+fn check_trusted_comment(dir: &Path, archive: &str, trusted_comment: &str) -> Vec<String> {
+    let fields = parse_trusted_comment(trusted_comment);
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find_map(|(k, value)| (*k == key).then_some(*value))
+    };
+    let mut problems = Vec::new();
+    if let Some(file) = field("file")
+        && file != archive
+    {
+        problems.push(format!("trusted comment names a different file: {file}"));
+    }
+    let stem = archive
+        .strip_suffix(&format!(".{ARCHIVE_EXTENSION}"))
+        .unwrap_or(archive);
+    let metadata_path = dir.join(format!("{stem}{METADATA_SUFFIX}"));
+    if !metadata_path.exists() {
+        return problems;
+    }
+    let metadata = match std::fs::read_to_string(&metadata_path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| toml::from_str::<toml::Table>(&contents).map_err(|e| e.to_string()))
+    {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            problems.push(format!(
+                "build metadata {metadata_path:?} is unreadable: {e}"
+            ));
+            return problems;
+        }
+    };
+    for (key, metadata_key) in [("crate", "crate_name"), ("version", "crate_version")] {
+        let expected = metadata.get(metadata_key).and_then(toml::Value::as_str);
+        if let Some(value) = field(key)
+            && expected != Some(value)
+        {
+            problems.push(format!(
+                "trusted comment {key} {value:?} does not match the build metadata ({expected:?})"
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use minisign::KeyPair;
+
+    use super::check_directory;
+    use crate::checksums::{ChecksumAlgorithm, format_checksum_file};
+    use crate::signatures::{key_id, signature_path};
+    use crate::temp_dir::TempDir;
+
+    fn sign(keypair: &KeyPair, path: &Path, trusted_comment: &str) {
+        let signature = minisign::sign(
+            Some(&keypair.pk),
+            &keypair.sk,
+            std::fs::read(path).unwrap().as_slice(),
+            Some(trusted_comment),
+            None,
+        )
+        .unwrap();
+        std::fs::write(signature_path(path), signature.to_bytes()).unwrap();
+    }
+
+    #[test]
+    fn detects_discrepancies() {
+        let keypair = KeyPair::generate_unencrypted_keypair().unwrap();
+        let retired = KeyPair::generate_unencrypted_keypair().unwrap();
+        let dir = TempDir::new("verify-test").unwrap();
+        std::fs::write(dir.join("good.tar"), b"good").unwrap();
+        sign(&keypair, &dir.join("good.tar"), "file:good.tar\tcrate:good");
+        std::fs::write(dir.join("good.build.toml"), "crate_name = \"good\"").unwrap();
+        std::fs::write(dir.join("renamed.tar"), b"renamed").unwrap();
+        sign(&keypair, &dir.join("renamed.tar"), "file:original.tar");
+        std::fs::write(dir.join("unsigned.tar"), b"unsigned").unwrap();
+        std::fs::write(dir.join("retired.tar"), b"retired").unwrap();
+        sign(&retired, &dir.join("retired.tar"), "file:retired.tar");
+        std::fs::write(dir.join("orphan.tar.sig"), b"").unwrap();
+        let checksums = format_checksum_file(
+            ChecksumAlgorithm::Sha256,
+            &[(String::from("good.tar"), b"tampered".to_vec())],
+        );
+        std::fs::write(dir.join("SHA256SUMS"), checksums).unwrap();
+        sign(&keypair, &dir.join("SHA256SUMS"), "file:SHA256SUMS");
+        std::fs::write(
+            dir.join("index.json"),
+            r#"{"verifying_key": "", "archives": [{"crate": "gone", "version": "1.0.0", "target": "t", "url": "", "sha256": "", "signature_url": "", "signature": ""}]}"#,
+        )
+        .unwrap();
+
+        let (verified, discrepancies) =
+            check_directory(&keypair.pk, std::slice::from_ref(&retired.pk), &dir).unwrap();
+        assert_eq!(verified, [dir.join("good.tar")]);
+        let problems = discrepancies
+            .iter()
+            .map(|d| {
+                (
+                    d.path.file_name().unwrap().to_string_lossy().into_owned(),
+                    d.problem.as_str(),
+                )
+            })
+            .collect::<Vec<(String, &str)>>();
+        for expected in [
+            (
+                "renamed.tar",
+                "trusted comment names a different file: original.tar",
+            ),
+            ("unsigned.tar", "archive is not signed"),
+            ("good.tar", "does not match its' checksum in SHA256SUMS"),
+            ("renamed.tar", "not covered by SHA256SUMS"),
+            ("orphan.tar.sig", "orphaned signature"),
+            ("index.json", "index is not signed"),
+            (
+                "index.json",
+                "lists gone 1.0.0 for t, but no archive matches its' checksum",
+            ),
+            (
+                "retired.tar",
+                &format!(
+                    "signed with retired key {}, re-sign it with `warehouseify rotate-key`",
+                    key_id(&retired.pk)
+                ),
+            ),
+        ] {
+            assert!(
+                problems.contains(&(String::from(expected.0), expected.1)),
+                "{expected:?} not in {problems:?}"
+            );
+        }
+        assert_eq!(problems.len(), 10, "{problems:?}");
+    }
+}